@@ -0,0 +1,427 @@
+//! When the authors commit: `--weekday-histogram`, `--when` and `--timeline`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use color_eyre::eyre;
+use owo_colors::Style;
+use serde::Serialize;
+
+use whos_done_that::Commit;
+
+use crate::authors::select_authors;
+use crate::output::{
+    display_author, get_author_csv_fields, get_author_csv_header, get_sparkline,
+    truncate_with_ellipsis, write_author_table, write_csv_table, write_hidden_entries_message,
+    write_json_report, write_markdown_table, write_markdown_title, MAX_TABLE_AUTHOR_WIDTH,
+};
+use crate::{Cli, Format, ReportOptions, TimelinePeriod};
+
+/// The days of the week, in the same order as `AuthorData::commits_per_weekday`.
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Width of the bar of the busiest day of the week, in characters.
+const MAX_WEEKDAY_BAR_WIDTH: usize = 30;
+
+/// The JSON output of `--weekday-histogram`, for a single author.
+#[derive(Serialize)]
+struct WeekdayHistogram<'a> {
+    author_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_email: Option<&'a str>,
+    num_commits: usize,
+    /// From Monday to Sunday
+    commits_per_weekday: [usize; 7],
+}
+
+/// Count the commits of each author from `commits` by day of the week, then write them
+/// to `writer`. Return the number of authors that were written.
+pub fn write_weekday_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let (mut authors_data, num_hidden_authors) = select_authors(cli, commits);
+    if !cli.show_emails {
+        authors_data.iter_mut().for_each(|a| a.author_email = None);
+    }
+
+    let mut header = vec!["Author", "Commits"];
+    header.extend(WEEKDAYS);
+    let rows: Vec<Vec<String>> = authors_data
+        .iter()
+        .map(|a| {
+            let mut row = vec![a.display_name(), a.num_commits.to_string()];
+            row.extend(a.commits_per_weekday.map(|n| n.to_string()));
+            row
+        })
+        .collect();
+
+    match options.format {
+        Format::Text => {
+            // The bars share the same scale, so that the authors can be compared
+            let max_commits = authors_data
+                .iter()
+                .flat_map(|a| a.commits_per_weekday)
+                .max()
+                .unwrap_or_default();
+            for a in &authors_data {
+                writeln!(
+                    writer,
+                    "{} has made {}:",
+                    options.palette.author(&a.display_name()),
+                    match a.num_commits {
+                        1 => "1 commit".to_string(),
+                        n => format!("{n} commits"),
+                    }
+                )?;
+                for (weekday, num_commits) in WEEKDAYS.iter().zip(a.commits_per_weekday) {
+                    // Bars made of blocks are hard to read without colors, e.g. in a file
+                    if !options.palette.enabled {
+                        writeln!(writer, "  {weekday} {num_commits}")?;
+                        continue;
+                    }
+                    let width = (num_commits * MAX_WEEKDAY_BAR_WIDTH).div_ceil(max_commits.max(1));
+                    let bar = options
+                        .palette
+                        .paint(&"█".repeat(width), Style::new().green());
+                    writeln!(writer, "  {weekday} {bar} {num_commits}")?;
+                }
+            }
+        }
+        Format::Table => write_author_table(writer, &header, rows, options.palette)?,
+        Format::Json => {
+            let histograms: Vec<WeekdayHistogram> = authors_data
+                .iter()
+                .map(|a| WeekdayHistogram {
+                    author_name: &a.author_name,
+                    author_email: a.author_email.as_deref(),
+                    num_commits: a.num_commits,
+                    commits_per_weekday: a.commits_per_weekday,
+                })
+                .collect();
+            write_json_report(writer, &histograms)?;
+        }
+        Format::Csv => {
+            let show_emails = authors_data.iter().any(|a| a.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.push("num_commits".to_string());
+            header.extend(WEEKDAYS.map(str::to_lowercase));
+            let records = authors_data.iter().map(|a| {
+                let mut record =
+                    get_author_csv_fields(&a.author_name, a.author_email.as_deref(), show_emails);
+                record.push(a.num_commits.to_string());
+                record.extend(a.commits_per_weekday.map(|n| n.to_string()));
+                record
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            write_markdown_table(writer, &header, &rows, &[0])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(authors_data.len())
+}
+
+/// The JSON output of `--when`, for a single author.
+#[derive(Serialize)]
+struct WhenHistogram<'a> {
+    author_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_email: Option<&'a str>,
+    num_commits: usize,
+    /// From 00:00 to 23:00
+    commits_per_hour: [usize; 24],
+    /// From Monday to Sunday
+    commits_per_weekday: [usize; 7],
+}
+
+/// Count the commits of each author from `commits` by hour of the day and by day of the week,
+/// then write them to `writer`. Return the number of authors that were written.
+pub fn write_when_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let (mut authors_data, num_hidden_authors) = select_authors(cli, commits);
+    if !cli.show_emails {
+        authors_data.iter_mut().for_each(|a| a.author_email = None);
+    }
+    let hours: Vec<String> = (0..24).map(|h| format!("{h:02}")).collect();
+
+    match options.format {
+        // 31 columns of numbers don't fit in a terminal, so both get sparklines
+        Format::Text | Format::Table => {
+            let names: Vec<String> = authors_data
+                .iter()
+                .map(|a| truncate_with_ellipsis(&a.display_name(), MAX_TABLE_AUTHOR_WIDTH))
+                .collect();
+            let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+            writeln!(
+                writer,
+                "{}  {:<24}  {:<7}  Commits",
+                " ".repeat(width),
+                "00-23",
+                "Mon-Sun"
+            )?;
+            for (name, a) in names.iter().zip(&authors_data) {
+                // Padded before painting, since the escape codes have no width
+                let padding = " ".repeat(width - name.chars().count());
+                writeln!(
+                    writer,
+                    "{}{padding}  {}  {}  {}",
+                    options.palette.author(name),
+                    get_sparkline(&a.commits_per_hour),
+                    get_sparkline(&a.commits_per_weekday),
+                    a.num_commits
+                )?;
+            }
+        }
+        Format::Json => {
+            let histograms: Vec<WhenHistogram> = authors_data
+                .iter()
+                .map(|a| WhenHistogram {
+                    author_name: &a.author_name,
+                    author_email: a.author_email.as_deref(),
+                    num_commits: a.num_commits,
+                    commits_per_hour: a.commits_per_hour,
+                    commits_per_weekday: a.commits_per_weekday,
+                })
+                .collect();
+            write_json_report(writer, &histograms)?;
+        }
+        Format::Csv => {
+            let show_emails = authors_data.iter().any(|a| a.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.push("num_commits".to_string());
+            header.extend(hours.iter().map(|h| format!("hour_{h}")));
+            header.extend(WEEKDAYS.map(str::to_lowercase));
+            let records = authors_data.iter().map(|a| {
+                let mut record =
+                    get_author_csv_fields(&a.author_name, a.author_email.as_deref(), show_emails);
+                record.push(a.num_commits.to_string());
+                record.extend(a.commits_per_hour.map(|n| n.to_string()));
+                record.extend(a.commits_per_weekday.map(|n| n.to_string()));
+                record
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            let mut header = vec!["Author", "Commits"];
+            header.extend(hours.iter().map(String::as_str));
+            header.extend(WEEKDAYS);
+            let rows: Vec<Vec<String>> = authors_data
+                .iter()
+                .map(|a| {
+                    let mut row = vec![a.display_name(), a.num_commits.to_string()];
+                    row.extend(a.commits_per_hour.map(|n| n.to_string()));
+                    row.extend(a.commits_per_weekday.map(|n| n.to_string()));
+                    row
+                })
+                .collect();
+            write_markdown_table(writer, &header, &rows, &[0])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(authors_data.len())
+}
+
+/// The JSON output of `--timeline`.
+#[derive(Serialize)]
+struct TimelineReport<'a> {
+    /// e.g. `2024-03`, `2024-Q1` or `2024`, from the oldest to the newest
+    pub periods: Vec<String>,
+    authors: Vec<AuthorTimeline<'a>>,
+}
+
+/// The commits of a single author in each period of `--timeline`.
+#[derive(Serialize)]
+struct AuthorTimeline<'a> {
+    author_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_email: Option<&'a str>,
+    num_commits: usize,
+    /// In the same order as the periods, with explicit zeros
+    commits_per_period: Vec<usize>,
+}
+
+/// Return the `(year, month or quarter)` of `period` that contains `date` (as YYYY-MM-DD).
+/// Return `None` if the date can't be parsed.
+fn get_period(date: &str, period: TimelinePeriod) -> Option<(u32, u32)> {
+    let year = date.get(..4)?.parse().ok()?;
+    let month: u32 = date.get(5..7)?.parse().ok()?;
+    match period {
+        TimelinePeriod::Monthly => Some((year, month)),
+        TimelinePeriod::Quarterly => Some((year, month.div_ceil(3))),
+        TimelinePeriod::Yearly => Some((year, 1)),
+    }
+}
+
+/// Return the name of a `(year, index)` period, as returned by `get_period`.
+pub fn get_period_name((year, index): (u32, u32), period: TimelinePeriod) -> String {
+    match period {
+        TimelinePeriod::Monthly => format!("{year}-{index:02}"),
+        TimelinePeriod::Quarterly => format!("{year}-Q{index}"),
+        TimelinePeriod::Yearly => year.to_string(),
+    }
+}
+
+/// Return every period from `first` to `last` (both included), as returned by `get_period`.
+fn get_periods(first: (u32, u32), last: (u32, u32), period: TimelinePeriod) -> Vec<(u32, u32)> {
+    let periods_per_year = match period {
+        TimelinePeriod::Monthly => 12,
+        TimelinePeriod::Quarterly => 4,
+        TimelinePeriod::Yearly => 1,
+    };
+
+    let mut periods = Vec::new();
+    let mut current = first;
+    while current <= last {
+        periods.push(current);
+        current = match current {
+            (year, index) if index >= periods_per_year => (year + 1, 1),
+            (year, index) => (year, index + 1),
+        };
+    }
+
+    periods
+}
+
+/// The number of commits of each author in each period, see `count_commits_per_period`.
+pub struct PeriodCounts<'a> {
+    /// Every period from the oldest to the newest commit, as returned by `get_period`
+    pub periods: Vec<(u32, u32)>,
+    /// By email, in the same order as `periods`
+    pub counts: HashMap<&'a str, Vec<usize>>,
+}
+
+/// Count the commits of each author in each period, from the oldest to the newest of `commits`.
+pub fn count_commits_per_period(commits: &[Commit], period: TimelinePeriod) -> PeriodCounts<'_> {
+    let commit_periods: Vec<(&str, (u32, u32))> = commits
+        .iter()
+        .filter_map(|c| Some((c.author_email.as_str(), get_period(&c.date, period)?)))
+        .collect();
+    let periods = match (
+        commit_periods.iter().map(|(_, p)| *p).min(),
+        commit_periods.iter().map(|(_, p)| *p).max(),
+    ) {
+        (Some(first), Some(last)) => get_periods(first, last, period),
+        _ => Vec::new(),
+    };
+    let period_indices: HashMap<(u32, u32), usize> =
+        periods.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let mut counts: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (email, commit_period) in &commit_periods {
+        let author_counts = counts
+            .entry(email)
+            .or_insert_with(|| vec![0; periods.len()]);
+        author_counts[period_indices[commit_period]] += 1;
+    }
+
+    PeriodCounts { periods, counts }
+}
+
+/// Count the commits of each author from `commits` in each period of `--timeline`,
+/// then write them to `writer`. Return the number of authors that were written.
+pub fn write_timeline_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let period = cli.timeline.unwrap_or(TimelinePeriod::Monthly);
+    let PeriodCounts { periods, counts } = count_commits_per_period(commits, period);
+
+    let (authors_data, num_hidden_authors) = select_authors(cli, commits);
+    let empty_counts = vec![0; periods.len()];
+    let timelines: Vec<AuthorTimeline> = authors_data
+        .iter()
+        .map(|a| {
+            let email = a.author_email.as_deref().unwrap_or_default();
+            AuthorTimeline {
+                author_name: &a.author_name,
+                author_email: a.author_email.as_deref().filter(|_| cli.show_emails),
+                num_commits: a.num_commits,
+                commits_per_period: counts.get(email).unwrap_or(&empty_counts).clone(),
+            }
+        })
+        .collect();
+    let period_names: Vec<String> = periods
+        .iter()
+        .map(|p| get_period_name(*p, period))
+        .collect();
+
+    match options.format {
+        Format::Text | Format::Table => {
+            if let (Some(first), Some(last)) = (period_names.first(), period_names.last()) {
+                writeln!(writer, "Commits from {first} to {last}:")?;
+            }
+            let names: Vec<String> = timelines
+                .iter()
+                .map(|t| {
+                    let name = display_author(t.author_name, t.author_email);
+                    truncate_with_ellipsis(&name, MAX_TABLE_AUTHOR_WIDTH)
+                })
+                .collect();
+            let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+            for (name, timeline) in names.iter().zip(&timelines) {
+                // Padded before painting, since the escape codes have no width
+                let padding = " ".repeat(width - name.chars().count());
+                writeln!(
+                    writer,
+                    "{}{padding}  {}  {}",
+                    options.palette.author(name),
+                    get_sparkline(&timeline.commits_per_period),
+                    timeline.num_commits
+                )?;
+            }
+        }
+        Format::Json => {
+            let report = TimelineReport {
+                periods: period_names,
+                authors: timelines,
+            };
+            serde_json::to_writer_pretty(&mut *writer, &report)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            let show_emails = timelines.iter().any(|t| t.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.extend(period_names);
+            let records = timelines.iter().map(|t| {
+                let mut record = get_author_csv_fields(t.author_name, t.author_email, show_emails);
+                record.extend(t.commits_per_period.iter().map(usize::to_string));
+                record
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            let mut header = vec!["Author"];
+            header.extend(period_names.iter().map(String::as_str));
+            let rows: Vec<Vec<String>> = timelines
+                .iter()
+                .map(|t| {
+                    let mut row = vec![display_author(t.author_name, t.author_email)];
+                    row.extend(t.commits_per_period.iter().map(usize::to_string));
+                    row
+                })
+                .collect();
+            write_markdown_table(writer, &header, &rows, &[0])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(authors_data.len())
+}
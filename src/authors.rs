@@ -0,0 +1,517 @@
+//! The default report: the stats of each author, computed from the history.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use color_eyre::eyre;
+use owo_colors::Style;
+use serde::Serialize;
+
+use whos_done_that::{
+    aggregate_by_author, compute_percentages, get_days_between, is_author_requested, percentage,
+    AuthorData, CodeEdits, Commit,
+};
+
+use crate::output::{
+    format_days, get_author_csv_fields, get_author_csv_header, truncate_with_ellipsis,
+    write_aligned_table, write_csv_table, write_hidden_entries_message, write_json_report,
+    write_markdown_table, write_markdown_title, Palette, MAX_TABLE_AUTHOR_WIDTH,
+};
+use crate::{Cli, Format, HalfLife, ReportOptions, SortBy};
+
+/// The stats of all of the analyzed authors combined.
+#[derive(Serialize)]
+struct Totals {
+    num_authors: usize,
+    num_commits: usize,
+    #[serde(flatten)]
+    code_edits: CodeEdits,
+}
+
+/// The JSON report, when the totals are requested.
+#[derive(Serialize)]
+struct ReportWithTotals<'a> {
+    authors: &'a [AuthorData],
+    totals: &'a Totals,
+}
+
+/// Compute the stats of each author from `commits`, then write them to `writer`
+/// according to the filters and sorting requested in `cli`.
+/// Return the number of authors that were written.
+pub fn write_author_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let mut authors_data = aggregate_by_author(commits);
+    if let Some(half_life) = &cli.half_life {
+        compute_scores(&mut authors_data, commits, half_life);
+    }
+    if cli.credit_coauthors {
+        count_coauthored_commits(&mut authors_data, commits);
+    }
+
+    // This must happen before any filtering, so that the shares are relative to the whole repo
+    compute_percentages(&mut authors_data);
+    sort_authors(&mut authors_data, cli.sort_by);
+    if cli.reverse {
+        authors_data.reverse();
+    }
+
+    // Authors that don't have any commit on the branch are never reported
+    authors_data.retain(|a| a.num_commits > 0);
+
+    // Like the percentages, the totals are about the whole repo.
+    // The copies of the co-authored commits are the same commits, which only
+    // have lines of their own when the lines are split
+    let mut totals = Totals {
+        num_authors: authors_data.len(),
+        num_commits: 0,
+        code_edits: CodeEdits::default(),
+    };
+    for commit in commits
+        .iter()
+        .filter(|c| !c.is_coauthored || cli.split_coauthored_lines)
+    {
+        if !commit.is_coauthored {
+            totals.num_commits += 1;
+        }
+        for file_change in &commit.file_changes {
+            totals.code_edits.add(file_change);
+        }
+    }
+
+    authors_data.retain(|a| {
+        let email = a.author_email.as_deref().unwrap_or_default();
+        is_author_requested(&a.author_name, email, &cli.author)
+    });
+
+    if let Some(min_commits) = cli.min_commits {
+        let num_authors = authors_data.len();
+        authors_data.retain(|a| a.num_commits >= min_commits);
+        let num_filtered = num_authors - authors_data.len();
+        if num_filtered > 0 {
+            tracing::info!("Filtered {num_filtered} authors below {min_commits} commits");
+        }
+    }
+
+    if let Some(min_lines) = cli.min_lines {
+        let num_authors = authors_data.len();
+        authors_data.retain(|a| a.code_edits.changed_lines() >= min_lines);
+        let num_filtered = num_authors - authors_data.len();
+        if num_filtered > 0 {
+            tracing::info!("Filtered {num_filtered} authors below {min_lines} changed lines");
+        }
+    }
+
+    if !cli.show_emails {
+        authors_data.iter_mut().for_each(|a| a.author_email = None);
+    }
+
+    let num_authors = authors_data.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        authors_data.truncate(top);
+    }
+    let num_hidden_authors = num_authors - authors_data.len();
+
+    // Without knowing the formula, the score would be meaningless
+    let score_note = cli.half_life.as_ref().map(|h| {
+        format!(
+            "Score: each commit counts as 0.5^(age / {}), so a commit made {} ago counts half as much as one made today",
+            h.text, h.text
+        )
+    });
+    if let (Some(note), Format::Text | Format::Table) = (&score_note, options.format) {
+        writeln!(writer, "{note}")?;
+        writeln!(writer)?;
+    }
+
+    match options.format {
+        Format::Text => write_text_report(writer, &authors_data, options.palette)?,
+        Format::Table => write_table_report(writer, &authors_data, options.palette)?,
+        Format::Json if cli.totals => {
+            let report = ReportWithTotals {
+                authors: &authors_data,
+                totals: &totals,
+            };
+            serde_json::to_writer_pretty(&mut *writer, &report)?;
+            writeln!(writer)?;
+        }
+        Format::Json => write_json_report(writer, &authors_data)?,
+        Format::Csv => {
+            let totals = cli.totals.then_some(&totals);
+            write_csv_report(writer, &authors_data, totals, options.delimiter)?
+        }
+        Format::Markdown => write_markdown_report(
+            writer,
+            &authors_data,
+            options.title.as_deref(),
+            score_note.as_deref(),
+        )?,
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    if matches!(options.format, Format::Text | Format::Table) {
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "Total: {} authors, {} commits, {}",
+            totals.num_authors,
+            totals.num_commits,
+            options.palette.code_edits(&totals.code_edits)
+        )?;
+    }
+
+    Ok(authors_data.len())
+}
+
+/// Return the authors of `commits` sorted according to `cli`, keeping the ones with
+/// enough commits (see `--min-commits`) and at most `--top` of them.
+/// Return the authors, along with how many were left out by `--top`.
+pub fn select_authors(cli: &Cli, commits: &[Commit]) -> (Vec<AuthorData>, usize) {
+    let mut authors_data = aggregate_by_author(commits);
+    sort_authors(&mut authors_data, cli.sort_by);
+    if cli.reverse {
+        authors_data.reverse();
+    }
+    if let Some(min_commits) = cli.min_commits {
+        authors_data.retain(|a| a.num_commits >= min_commits);
+    }
+
+    let num_authors = authors_data.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        authors_data.truncate(top);
+    }
+
+    let num_hidden_authors = num_authors - authors_data.len();
+    (authors_data, num_hidden_authors)
+}
+
+/// Fill the recency weighted score of each author, where each of their `commits`
+/// counts as `0.5^(age / half_life)`. Commits from the future (e.g. because of clock skew)
+/// count as if they were made now.
+pub fn compute_scores(authors_data: &mut [AuthorData], commits: &[Commit], half_life: &HalfLife) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    for commit in commits {
+        let age = (now - commit.timestamp).max(0) as f64;
+        *scores.entry(&commit.author_email).or_default() += 0.5_f64.powf(age / half_life.seconds);
+    }
+
+    for author_data in authors_data {
+        let email = author_data.author_email.as_deref().unwrap_or_default();
+        let score = scores.get(email).copied().unwrap_or(0.0);
+        author_data.score = Some((score * 100.0).round() / 100.0);
+    }
+}
+
+/// Fill the number of `commits` of each author that were credited to them as a co-author.
+pub fn count_coauthored_commits(authors_data: &mut [AuthorData], commits: &[Commit]) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for commit in commits.iter().filter(|c| c.is_coauthored) {
+        *counts.entry(&commit.author_email).or_default() += 1;
+    }
+
+    for author_data in authors_data {
+        let email = author_data.author_email.as_deref().unwrap_or_default();
+        author_data.coauthored_commits = Some(counts.get(email).copied().unwrap_or(0));
+    }
+}
+
+/// Sort `authors_data` in descending order according to `sort_by`
+/// (except for names, which are sorted alphabetically).
+/// Ties are broken by author name (ascending) so that the output is deterministic.
+pub fn sort_authors(authors_data: &mut [AuthorData], sort_by: SortBy) {
+    authors_data.sort_by(|a, b| {
+        let ordering = match sort_by {
+            SortBy::Score => b.score.unwrap_or(0.0).total_cmp(&a.score.unwrap_or(0.0)),
+            SortBy::Commits => b.num_commits.cmp(&a.num_commits),
+            SortBy::Additions => b.code_edits.additions.cmp(&a.code_edits.additions),
+            SortBy::Removals => b.code_edits.removals.cmp(&a.code_edits.removals),
+            SortBy::Net => b.net_lines().cmp(&a.net_lines()),
+            SortBy::ActiveDays => b.active_days.cmp(&a.active_days),
+            SortBy::Recency => b.last_commit_timestamp.cmp(&a.last_commit_timestamp),
+            SortBy::Name => std::cmp::Ordering::Equal,
+        };
+
+        ordering.then_with(|| a.author_name.cmp(&b.author_name))
+    });
+}
+
+/// Write one human readable line per author to `writer`.
+fn write_text_report(
+    writer: &mut impl Write,
+    authors_data: &[AuthorData],
+    palette: Palette,
+) -> eyre::Result<()> {
+    for author_data in authors_data {
+        let mut ending = match author_data.num_commits {
+            1 => "1 commit".to_string(),
+            _ => format!("{} commits", author_data.num_commits),
+        };
+        if let Some(n @ 1..) = author_data.coauthored_commits {
+            ending.push_str(&format!(" ({n} co-authored)"));
+        }
+
+        let score = match author_data.score {
+            Some(score) => format!(", score {score:.2}"),
+            None => String::new(),
+        };
+        let dates = match (
+            author_data.first_commit_date(),
+            author_data.last_commit_date(),
+        ) {
+            (Some(first), Some(last)) => {
+                let days = match author_data.active_days {
+                    1 => "1 day".to_string(),
+                    n => format!("{n} days"),
+                };
+                format!(", active on {days} from {first} to {last}")
+            }
+            _ => String::new(),
+        };
+        let commit_sizes = format!(
+            ", {:.1} lines changed per commit on average and {} at most",
+            author_data.avg_lines_per_commit, author_data.largest_commit_lines
+        );
+        writeln!(
+            writer,
+            "{} has made {ending}: {} ({:.1}% of commits, {:.1}% of additions{score}){dates}{commit_sizes}",
+            palette.author(&author_data.display_name()),
+            palette.code_edits(&author_data.code_edits),
+            author_data.percent_commits,
+            author_data.percent_additions
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Return the header and the rows of the table of `authors_data`, shared by the
+/// aligned and the markdown tables. The optional columns are only there when computed.
+fn get_author_table(authors_data: &[AuthorData]) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let mut header = vec![
+        "Author",
+        "Commits",
+        "Additions",
+        "Removals",
+        "% Commits",
+        "% Additions",
+        "First commit",
+        "Last commit",
+        "Active days",
+        "Active span",
+    ];
+    let show_coauthored = authors_data.iter().any(|a| a.coauthored_commits.is_some());
+    if show_coauthored {
+        header.push("Co-authored");
+    }
+    let show_scores = authors_data.iter().any(|a| a.score.is_some());
+    if show_scores {
+        header.push("Score");
+    }
+    let rows: Vec<Vec<String>> = authors_data
+        .iter()
+        .map(|a| {
+            let mut row = vec![
+                a.display_name(),
+                a.num_commits.to_string(),
+                a.code_edits.additions.to_string(),
+                a.code_edits.removals.to_string(),
+                format!("{:.1}", a.percent_commits),
+                format!("{:.1}", a.percent_additions),
+                a.first_commit_date().unwrap_or_default().to_string(),
+                a.last_commit_date().unwrap_or_default().to_string(),
+                a.active_days.to_string(),
+                format_days(a.active_span_days),
+            ];
+            if show_coauthored {
+                row.push(a.coauthored_commits.unwrap_or(0).to_string());
+            }
+            if show_scores {
+                row.push(format!("{:.2}", a.score.unwrap_or(0.0)));
+            }
+            row
+        })
+        .collect();
+
+    (header, rows)
+}
+
+/// Write `authors_data` to `writer` as a table with aligned columns,
+/// where the numbers are aligned to the right.
+fn write_table_report(
+    writer: &mut impl Write,
+    authors_data: &[AuthorData],
+    palette: Palette,
+) -> eyre::Result<()> {
+    let (header, mut rows) = get_author_table(authors_data);
+    for row in &mut rows {
+        row[0] = truncate_with_ellipsis(&row[0], MAX_TABLE_AUTHOR_WIDTH);
+    }
+
+    let styles = [
+        Style::new().bold(),
+        Style::new(),
+        Style::new().green(),
+        Style::new().red(),
+    ];
+    write_aligned_table(writer, &header, &rows, 1, &styles, palette)
+}
+
+/// Write `authors_data` to `writer` as CSV, one row per author followed by
+/// the `totals` (if any), using `delimiter` to separate the fields.
+fn write_csv_report(
+    writer: &mut impl Write,
+    authors_data: &[AuthorData],
+    totals: Option<&Totals>,
+    delimiter: u8,
+) -> eyre::Result<()> {
+    let show_emails = authors_data.iter().any(|a| a.author_email.is_some());
+
+    let mut header = get_author_csv_header(show_emails);
+    header.extend(
+        [
+            "num_commits",
+            "additions",
+            "removals",
+            "binary_changes",
+            "percent_commits",
+            "percent_additions",
+            "first_commit",
+            "last_commit",
+            "active_days",
+            "active_span_days",
+            "avg_lines_per_commit",
+            "largest_commit_lines",
+        ]
+        .map(String::from),
+    );
+    let show_coauthored = authors_data.iter().any(|a| a.coauthored_commits.is_some());
+    if show_coauthored {
+        header.push("coauthored_commits".to_string());
+    }
+    let show_scores = authors_data.iter().any(|a| a.score.is_some());
+    if show_scores {
+        header.push("score".to_string());
+    }
+
+    let mut records = Vec::new();
+    for author_data in authors_data {
+        let mut record = get_author_csv_fields(
+            &author_data.author_name,
+            author_data.author_email.as_deref(),
+            show_emails,
+        );
+        record.extend([
+            author_data.num_commits.to_string(),
+            author_data.code_edits.additions.to_string(),
+            author_data.code_edits.removals.to_string(),
+            author_data.code_edits.binary_changes.to_string(),
+            format!("{:.1}", author_data.percent_commits),
+            format!("{:.1}", author_data.percent_additions),
+            author_data
+                .first_commit_date()
+                .unwrap_or_default()
+                .to_string(),
+            author_data
+                .last_commit_date()
+                .unwrap_or_default()
+                .to_string(),
+            author_data.active_days.to_string(),
+            author_data.active_span_days.to_string(),
+            format!("{:.1}", author_data.avg_lines_per_commit),
+            author_data.largest_commit_lines.to_string(),
+        ]);
+        if show_coauthored {
+            record.push(author_data.coauthored_commits.unwrap_or(0).to_string());
+        }
+        if show_scores {
+            record.push(format!("{:.2}", author_data.score.unwrap_or(0.0)));
+        }
+        records.push(record);
+    }
+
+    if let Some(totals) = totals {
+        let first_commit = authors_data
+            .iter()
+            .filter_map(AuthorData::first_commit_date)
+            .min()
+            .unwrap_or_default()
+            .to_string();
+        let last_commit = authors_data
+            .iter()
+            .filter_map(AuthorData::last_commit_date)
+            .max()
+            .unwrap_or_default()
+            .to_string();
+        let active_span_days = get_days_between(&first_commit, &last_commit);
+
+        let mut record = get_author_csv_fields("Total", None, show_emails);
+        record.extend([
+            totals.num_commits.to_string(),
+            totals.code_edits.additions.to_string(),
+            totals.code_edits.removals.to_string(),
+            totals.code_edits.binary_changes.to_string(),
+            format!("{:.1}", percentage(totals.num_commits, totals.num_commits)),
+            format!(
+                "{:.1}",
+                percentage(totals.code_edits.additions, totals.code_edits.additions)
+            ),
+            first_commit,
+            last_commit,
+            // Days can be shared between authors, so they don't add up
+            String::new(),
+            active_span_days.to_string(),
+            format!(
+                "{:.1}",
+                totals.code_edits.changed_lines() as f64 / totals.num_commits.max(1) as f64
+            ),
+            authors_data
+                .iter()
+                .map(|a| a.largest_commit_lines)
+                .max()
+                .unwrap_or_default()
+                .to_string(),
+        ]);
+        if show_coauthored {
+            let total: usize = authors_data
+                .iter()
+                .filter_map(|a| a.coauthored_commits)
+                .sum();
+            record.push(total.to_string());
+        }
+        if show_scores {
+            let total_score: f64 = authors_data.iter().filter_map(|a| a.score).sum();
+            record.push(format!("{total_score:.2}"));
+        }
+        records.push(record);
+    }
+
+    write_csv_table(writer, delimiter, header, records)
+}
+
+/// Write `authors_data` to `writer` as a GitHub flavored markdown table,
+/// with the numeric columns aligned to the right.
+/// If a `title` is given, it's written as a heading before the table.
+fn write_markdown_report(
+    writer: &mut impl Write,
+    authors_data: &[AuthorData],
+    title: Option<&str>,
+    score_note: Option<&str>,
+) -> eyre::Result<()> {
+    write_markdown_title(writer, title)?;
+    if let Some(score_note) = score_note {
+        writeln!(writer, "_{score_note}_")?;
+        writeln!(writer)?;
+    }
+
+    let (header, rows) = get_author_table(authors_data);
+    // The dates are aligned to the left, like the text
+    write_markdown_table(writer, &header, &rows, &[0, 6, 7])
+}
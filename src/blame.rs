@@ -0,0 +1,152 @@
+//! `--mode blame`: the lines of the current files that each author touched last.
+
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+
+use color_eyre::eyre;
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+
+use whos_done_that::{
+    get_blame_data, get_text_files, is_author_excluded, is_author_requested, percentage,
+    QueryOptions,
+};
+
+use crate::exclusions::{is_path_excluded, PathPattern};
+use crate::output::{
+    get_author_csv_fields, get_author_csv_header, write_author_table, write_csv_table,
+    write_hidden_entries_message, write_json_report, write_markdown_table, write_markdown_title,
+};
+use crate::{into_report, Cli, Format, ReportOptions};
+
+/// Blame all of the text files of the analyzed branch, then write the number of lines
+/// owned by each author to `writer`. Return the number of authors that were written.
+pub fn write_blame_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    target_dir: &Path,
+    query_options: &QueryOptions,
+    exclude_patterns: &[Regex],
+    excluded_paths: &[PathPattern],
+) -> eyre::Result<usize> {
+    tracing::info!("Blaming files..");
+    let mut files = get_text_files(target_dir, query_options).map_err(into_report)?;
+    files.retain(|f| !is_path_excluded(f, excluded_paths));
+
+    let progress_bar = if !cli.no_progress && std::io::stderr().is_terminal() {
+        ProgressBar::new(files.len() as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{bar:40} Blamed {pos}/{len} files ({elapsed}, about {eta} left)",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    let mut blame_data = get_blame_data(target_dir, query_options, &files, |_| {
+        progress_bar.inc(1);
+    });
+    progress_bar.finish_and_clear();
+
+    blame_data.retain(|b| {
+        let email = b.author_email.as_deref().unwrap_or_default();
+        !is_author_excluded(&b.author_name, email, exclude_patterns)
+    });
+
+    // Like for the history, the shares are relative to the whole repo
+    let total_lines: usize = blame_data.iter().map(|b| b.lines_owned).sum();
+    for b in &mut blame_data {
+        b.percent_lines = percentage(b.lines_owned, total_lines);
+    }
+
+    for author in &cli.author {
+        let patterns = std::slice::from_ref(author);
+        if !blame_data.iter().any(|b| {
+            is_author_requested(
+                &b.author_name,
+                b.author_email.as_deref().unwrap_or_default(),
+                patterns,
+            )
+        }) {
+            tracing::warn!("No author matches '{author}'");
+        }
+    }
+    blame_data.retain(|b| {
+        let email = b.author_email.as_deref().unwrap_or_default();
+        is_author_requested(&b.author_name, email, &cli.author)
+    });
+    blame_data.sort_by(|a, b| {
+        b.lines_owned
+            .cmp(&a.lines_owned)
+            .then_with(|| a.author_name.cmp(&b.author_name))
+    });
+    if cli.reverse {
+        blame_data.reverse();
+    }
+
+    if let Some(min_lines) = cli.min_lines {
+        blame_data.retain(|b| b.lines_owned >= min_lines);
+    }
+    if !cli.show_emails {
+        blame_data.iter_mut().for_each(|b| b.author_email = None);
+    }
+
+    let num_authors = blame_data.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        blame_data.truncate(top);
+    }
+    let num_hidden_authors = num_authors - blame_data.len();
+
+    let header = ["Author", "Lines", "% Lines"];
+    let rows: Vec<Vec<String>> = blame_data
+        .iter()
+        .map(|b| {
+            vec![
+                b.display_name(),
+                b.lines_owned.to_string(),
+                format!("{:.1}", b.percent_lines),
+            ]
+        })
+        .collect();
+
+    match options.format {
+        Format::Text => {
+            for b in &blame_data {
+                let lines = match b.lines_owned {
+                    1 => "1 line".to_string(),
+                    n => format!("{n} lines"),
+                };
+                writeln!(
+                    writer,
+                    "{} owns {lines} ({:.1}% of the lines)",
+                    options.palette.author(&b.display_name()),
+                    b.percent_lines
+                )?;
+            }
+        }
+        Format::Table => write_author_table(writer, &header, rows, options.palette)?,
+        Format::Json => write_json_report(writer, &blame_data)?,
+        Format::Csv => {
+            let show_emails = blame_data.iter().any(|b| b.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.extend(["lines_owned".to_string(), "percent_lines".to_string()]);
+            let records = blame_data.iter().map(|b| {
+                let mut record =
+                    get_author_csv_fields(&b.author_name, b.author_email.as_deref(), show_emails);
+                record.extend([b.lines_owned.to_string(), format!("{:.1}", b.percent_lines)]);
+                record
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            write_markdown_table(writer, &header, &rows, &[0])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(blame_data.len())
+}
@@ -0,0 +1,197 @@
+//! The `bus-factor` command.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use color_eyre::eyre;
+use owo_colors::Style;
+use serde::Serialize;
+
+use whos_done_that::{aggregate_by_author, get_names_by_email, Commit};
+
+use crate::files::get_parent_directory;
+use crate::{BusFactorArgs, BusFactorMetric, Cli, Format, ReportOptions};
+
+/// The bus factor of a directory, `None` when nobody changed anything in it.
+#[derive(Serialize)]
+struct DirectoryBusFactor {
+    path: String,
+    bus_factor: Option<usize>,
+    /// The authors that, together, account for the threshold
+    authors: Vec<String>,
+}
+
+/// The JSON output of the `bus-factor` subcommand.
+#[derive(Serialize)]
+struct BusFactorReport {
+    threshold: f64,
+    metric: BusFactorMetric,
+    bus_factor: Option<usize>,
+    authors: Vec<String>,
+    directories: Vec<DirectoryBusFactor>,
+}
+
+/// Compute the bus factor of the whole repo and of each directory (grouped by `--depth`),
+/// then write them to `writer`. Directories with a bus factor of 1 are highlighted,
+/// since they depend on a single person. Return the number of directories that were written.
+pub fn write_bus_factor_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    args: &BusFactorArgs,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let repo_contributions: Vec<(String, usize)> = aggregate_by_author(commits)
+        .into_iter()
+        .map(|a| {
+            let contribution = match args.metric {
+                BusFactorMetric::Additions => a.code_edits.additions,
+                BusFactorMetric::Commits => a.num_commits,
+            };
+            (a.author_name, contribution)
+        })
+        .collect();
+    let repo_authors = get_bus_factor_authors(repo_contributions, args.threshold);
+    let repo_bus_factor = repo_authors.as_ref().map(Vec::len);
+
+    let mut directories: Vec<DirectoryBusFactor> =
+        get_contributions_by_directory(commits, cli.depth, args.metric)
+            .into_iter()
+            .map(|(path, contributions)| {
+                let authors = get_bus_factor_authors(contributions, args.threshold);
+                DirectoryBusFactor {
+                    path,
+                    bus_factor: authors.as_ref().map(Vec::len),
+                    authors: authors.unwrap_or_default(),
+                }
+            })
+            .collect();
+    // The riskiest directories come first, while the ones without any data come last
+    directories.sort_by(|a, b| {
+        (a.bus_factor.is_none(), a.bus_factor, &a.path).cmp(&(
+            b.bus_factor.is_none(),
+            b.bus_factor,
+            &b.path,
+        ))
+    });
+
+    if options.format == Format::Json {
+        let report = BusFactorReport {
+            threshold: args.threshold,
+            metric: args.metric,
+            bus_factor: repo_bus_factor,
+            authors: repo_authors.unwrap_or_default(),
+            directories,
+        };
+        serde_json::to_writer_pretty(&mut *writer, &report)?;
+        writeln!(writer)?;
+        return Ok(report.directories.len());
+    }
+
+    let format_bus_factor = |bus_factor: Option<usize>, authors: &[String]| {
+        let authors: Vec<String> = authors.iter().map(|a| options.palette.author(a)).collect();
+        match bus_factor {
+            Some(1) => {
+                let risk = options
+                    .palette
+                    .paint("1, at risk!", Style::new().red().bold());
+                format!("{risk} ({})", authors.join(", "))
+            }
+            Some(n) => format!("{n} ({})", authors.join(", ")),
+            None => "n/a".to_string(),
+        }
+    };
+    let metric = match args.metric {
+        BusFactorMetric::Additions => "additions",
+        BusFactorMetric::Commits => "commits",
+    };
+
+    writeln!(
+        writer,
+        "Bus factor of the repo, covering {}% of the {metric}: {}",
+        // Rounded, since e.g. 0.57 * 100 isn't exactly 57
+        (args.threshold * 1000.0).round() / 10.0,
+        format_bus_factor(repo_bus_factor, repo_authors.as_deref().unwrap_or_default())
+    )?;
+    for directory in &directories {
+        writeln!(
+            writer,
+            "{}: {}",
+            directory.path,
+            format_bus_factor(directory.bus_factor, &directory.authors)
+        )?;
+    }
+
+    Ok(directories.len())
+}
+
+/// Return the contributions of each author (by name) to each directory (grouped by `depth`),
+/// measured by `metric`. A commit counts once per directory, however many files it touched.
+fn get_contributions_by_directory(
+    commits: &[Commit],
+    depth: usize,
+    metric: BusFactorMetric,
+) -> HashMap<String, Vec<(String, usize)>> {
+    let names_by_email = get_names_by_email(commits);
+    let mut contributions: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+
+    for commit in commits {
+        let mut touched_directories: Vec<String> = Vec::new();
+        for file_change in &commit.file_changes {
+            let directory = get_parent_directory(&file_change.path, depth);
+            let contribution = contributions
+                .entry(directory.clone())
+                .or_default()
+                .entry(&commit.author_email)
+                .or_default();
+
+            match metric {
+                BusFactorMetric::Additions => *contribution += file_change.additions,
+                BusFactorMetric::Commits if !touched_directories.contains(&directory) => {
+                    *contribution += 1;
+                    touched_directories.push(directory);
+                }
+                BusFactorMetric::Commits => {}
+            }
+        }
+    }
+
+    contributions
+        .into_iter()
+        .map(|(directory, by_author)| {
+            let contributions = by_author
+                .into_iter()
+                .map(|(email, contribution)| (names_by_email[email].to_string(), contribution))
+                .collect();
+            (directory, contributions)
+        })
+        .collect()
+}
+
+/// Return the smallest group of authors whose `contributions`, summed up together,
+/// account for at least `threshold` (between 0 and 1) of the total, from the biggest
+/// contributor to the smallest. The size of the group is the bus factor.
+/// Return `None` if there's nothing to account for.
+fn get_bus_factor_authors(
+    mut contributions: Vec<(String, usize)>,
+    threshold: f64,
+) -> Option<Vec<String>> {
+    let total: usize = contributions.iter().map(|(_, c)| c).sum();
+    if total == 0 {
+        return None;
+    }
+
+    contributions.sort_by(|(name_a, a), (name_b, b)| b.cmp(a).then_with(|| name_a.cmp(name_b)));
+    let target = total as f64 * threshold;
+    let mut covered = 0;
+    let mut authors = Vec::new();
+    for (name, contribution) in contributions {
+        covered += contribution;
+        authors.push(name);
+        if covered as f64 >= target {
+            break;
+        }
+    }
+
+    Some(authors)
+}
@@ -0,0 +1,137 @@
+//! `--cache`: the commits read from a history are stored on disk, so that they're reused
+//! as long as the history (and the options) don't change.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre, eyre::WrapErr, Section};
+use serde::{Deserialize, Serialize};
+
+use whos_done_that::{get_stdout_from_git_or_fail, Commit, QueryOptions};
+
+use crate::into_report;
+
+/// Bumped whenever the format of the cached commits changes, so that old caches are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// The commits of a repo, as stored in the cache of `--cache`.
+#[derive(Serialize, Deserialize)]
+struct CachedCommits<C> {
+    /// Everything the commits depend on, which tells apart the (unlikely) hash collisions
+    key: String,
+    commits: C,
+}
+
+/// Return the directory of the cache of `--cache`, following the XDG conventions.
+fn get_cache_dir() -> eyre::Result<PathBuf> {
+    let base_dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok_or_else(|| eyre::eyre!("Failed to find a directory for the cache"))
+        .with_suggestion(|| "Set 'XDG_CACHE_HOME' to the directory where it should be kept")?;
+
+    Ok(base_dir.join("whos-done-that"))
+}
+
+/// Where the commits of a repo are cached, see `--cache`.
+pub struct CacheEntry {
+    path: PathBuf,
+    /// See `CachedCommits::key`
+    key: String,
+}
+
+impl CacheEntry {
+    /// Return the cached commits, or `None` if there aren't any.
+    /// A cache that can't be read is treated like a missing one, since it's rebuilt anyway.
+    pub fn read(&self) -> Option<Vec<Commit>> {
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str::<CachedCommits<Vec<Commit>>>(&contents) {
+            Ok(cached) if cached.key == self.key => Some(cached.commits),
+            Ok(_) => None,
+            Err(err) => {
+                let path = self.path.display();
+                tracing::debug!("Ignoring the invalid cache at '{path}': {err}");
+                None
+            }
+        }
+    }
+
+    /// Save `commits` in the cache.
+    pub fn write(&self, commits: &[Commit]) -> eyre::Result<()> {
+        let error_message = || format!("Failed to write the cache at '{}'", self.path.display());
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).wrap_err_with(error_message)?;
+        }
+
+        // Written next to the final file first, so that an interrupted run doesn't leave
+        // a truncated cache behind
+        let temp_path = self.path.with_extension("json.tmp");
+        let cached = CachedCommits {
+            key: self.key.clone(),
+            commits,
+        };
+        std::fs::write(&temp_path, serde_json::to_vec(&cached)?).wrap_err_with(error_message)?;
+        std::fs::rename(&temp_path, &self.path).wrap_err_with(error_message)?;
+        tracing::debug!(
+            "Cached {} commits at '{}'",
+            commits.len(),
+            self.path.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Return where the commits of the repo at `target_dir` that match `options` are cached.
+/// The key includes the commits that the revisions point to, so that the cache is
+/// invalidated as soon as they move.
+pub fn get_cache_entry(target_dir: &Path, options: &QueryOptions) -> eyre::Result<CacheEntry> {
+    let revisions = get_stdout_from_git_or_fail(target_dir, ["rev-parse", options.revisions()])
+        .map_err(into_report)?;
+    let top_level = get_stdout_from_git_or_fail(target_dir, ["rev-parse", "--show-toplevel"])
+        .map_err(into_report)?;
+
+    let mut key = format!(
+        "{CACHE_VERSION}\n{}\n{revisions}\n{}",
+        std::fs::canonicalize(target_dir)?.display(),
+        serde_json::to_string(options)?
+    );
+    // These aren't necessarily committed, yet they change the identities and the files
+    for file_name in [".mailmap", ".gitattributes"] {
+        let contents = std::fs::read(Path::new(&top_level).join(file_name)).unwrap_or_default();
+        key.push_str(&format!("\n{}", String::from_utf8_lossy(&contents)));
+    }
+    // Relative dates like '2 weeks ago' select different commits every day
+    if options.since.is_some() || options.until.is_some() {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+        key.push_str(&format!("\n{}", now.as_secs() / (24 * 60 * 60)));
+    }
+
+    let mut hasher = std::hash::DefaultHasher::new();
+    std::hash::Hash::hash(&key, &mut hasher);
+    let file_name = format!("{:016x}.json", std::hash::Hasher::finish(&hasher));
+
+    Ok(CacheEntry {
+        path: get_cache_dir()?.join(file_name),
+        key,
+    })
+}
+
+/// Delete the cache of `--cache`, see `--clear-cache`.
+pub fn clear_cache() -> eyre::Result<()> {
+    let cache_dir = get_cache_dir()?;
+    match std::fs::remove_dir_all(&cache_dir) {
+        Ok(()) => tracing::info!("Deleted the cache at '{}'", cache_dir.display()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::info!("There's no cache at '{}'", cache_dir.display());
+        }
+        Err(err) => {
+            return Err(err).wrap_err_with(|| {
+                format!("Failed to delete the cache at '{}'", cache_dir.display())
+            });
+        }
+    }
+
+    Ok(())
+}
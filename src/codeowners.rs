@@ -0,0 +1,379 @@
+//! The `codeowners` and `check-codeowners` commands.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre, eyre::WrapErr, Section};
+use regex::Regex;
+
+use whos_done_that::{aggregate_by_path, Commit, QueryOptions};
+
+use crate::files::get_parent_directory;
+use crate::{CheckCodeownersArgs, Cli, CodeownersArgs};
+
+/// Write a CODEOWNERS file to `writer`, listing the top authors of each directory
+/// (grouped by `--depth`) as its owners. Return the number of rules that were written.
+pub fn write_codeowners(
+    writer: &mut impl Write,
+    cli: &Cli,
+    args: &CodeownersArgs,
+    commits: &[Commit],
+    query_options: &QueryOptions,
+) -> eyre::Result<usize> {
+    let handles = read_handle_map(&args.handle_map)?;
+    let mut directories_data = aggregate_by_path(commits, usize::MAX, |path| {
+        get_parent_directory(path, cli.depth)
+    });
+    // Later rules take precedence, so the catch-all one for the root must come first
+    directories_data.sort_by(|a, b| (a.path != ".", &a.path).cmp(&(b.path != ".", &b.path)));
+
+    writeln!(
+        writer,
+        "# Generated by whos-done-that {} from the history of {}",
+        env!("CARGO_PKG_VERSION"),
+        query_options.describe_revisions()
+    )?;
+    writeln!(
+        writer,
+        "# Depth: {}, minimum share: {}%, at most {} owners per directory",
+        cli.depth, args.min_share, args.max_owners
+    )?;
+    writeln!(writer)?;
+
+    let mut authors_without_handle: Vec<String> = Vec::new();
+    let mut num_rules = 0;
+    for directory_data in &directories_data {
+        let mut owners: Vec<&str> = Vec::new();
+        let eligible_authors = directory_data
+            .authors
+            .iter()
+            .filter(|a| a.percent_changed_lines >= args.min_share);
+
+        for author in eligible_authors {
+            let email = author.author_email.as_deref().unwrap_or_default();
+            let handle = handles
+                .get(&author.author_name)
+                .or_else(|| handles.get(email));
+
+            match handle {
+                // The same person might have committed with multiple emails
+                Some(handle) if owners.contains(&handle.as_str()) => {}
+                Some(handle) => owners.push(handle),
+                None => {
+                    let display_name = author.display_name();
+                    if !authors_without_handle.contains(&display_name) {
+                        tracing::warn!("No handle found for {display_name}, leaving them out");
+                        authors_without_handle.push(display_name);
+                    }
+                }
+            }
+            if owners.len() == args.max_owners {
+                break;
+            }
+        }
+
+        if owners.is_empty() {
+            tracing::debug!("No owners found for '{}'", directory_data.path);
+            continue;
+        }
+
+        writeln!(
+            writer,
+            "{} {}",
+            get_codeowners_pattern(&directory_data.path),
+            owners.join(" ")
+        )?;
+        num_rules += 1;
+    }
+
+    Ok(num_rules)
+}
+
+/// Read the TOML file at `path`, mapping author names (or emails) to GitHub handles.
+fn read_handle_map(path: &Path) -> eyre::Result<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read the handle map at '{}'", path.display()))?;
+
+    toml::from_str(&contents)
+        .wrap_err_with(|| format!("Failed to parse the handle map at '{}'", path.display()))
+        .with_suggestion(|| "Each line should look like '\"Jane Doe\" = \"@janedoe\"'")
+}
+
+/// Return the CODEOWNERS pattern that matches everything inside `directory`,
+/// where `.` is the root of the repo.
+fn get_codeowners_pattern(directory: &str) -> String {
+    if directory == "." {
+        return "*".to_string();
+    }
+
+    // Spaces would otherwise separate the pattern from the owners
+    format!("/{}/", directory.replace(' ', "\\ "))
+}
+
+/// A single non-empty line of a CODEOWNERS file.
+struct CodeownersEntry {
+    line_number: usize,
+    pattern: String,
+    owners: Vec<String>,
+}
+
+/// Check that the owners of each entry of the CODEOWNERS file of the repo at `target_dir`
+/// made at least `--min-commits` commits touching the files matched by the entry,
+/// then write the ones that didn't to `writer`. Return how many of them were found.
+pub fn write_codeowners_check(
+    writer: &mut impl Write,
+    target_dir: &Path,
+    args: &CheckCodeownersArgs,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let codeowners_path = match &args.codeowners {
+        Some(path) => path.clone(),
+        None => find_codeowners(target_dir)?,
+    };
+    let contents = std::fs::read_to_string(&codeowners_path).wrap_err_with(|| {
+        format!(
+            "Failed to read the CODEOWNERS file at '{}'",
+            codeowners_path.display()
+        )
+    })?;
+    let handles = match &args.handle_map {
+        Some(path) => read_handle_map(path)?,
+        None => HashMap::new(),
+    };
+
+    let mut num_stale_owners = 0;
+    for entry in parse_codeowners(&contents) {
+        let regex = get_codeowners_regex(&entry.pattern).wrap_err_with(|| {
+            format!(
+                "Invalid pattern '{}' at line {} of '{}'",
+                entry.pattern,
+                entry.line_number,
+                codeowners_path.display()
+            )
+        })?;
+        let matching_commits: Vec<&Commit> = commits
+            .iter()
+            .filter(|c| c.file_changes.iter().any(|f| regex.is_match(&f.path)))
+            .collect();
+
+        for owner in &entry.owners {
+            // Teams would need to be resolved via the GitHub API
+            if owner.starts_with('@') && owner.contains('/') {
+                tracing::debug!("Skipping team '{owner}' at line {}", entry.line_number);
+                continue;
+            }
+            if owner.starts_with('@') && !handles.values().any(|h| h.eq_ignore_ascii_case(owner)) {
+                tracing::warn!(
+                    "Can't check '{owner}' at line {}, since it's not in the handle map",
+                    entry.line_number
+                );
+                continue;
+            }
+
+            let num_commits = matching_commits
+                .iter()
+                .filter(|c| is_codeowner(c, owner, &handles))
+                .count();
+            if num_commits < args.min_commits {
+                let ending = match num_commits {
+                    1 => "1 commit".to_string(),
+                    _ => format!("{num_commits} commits"),
+                };
+                writeln!(
+                    writer,
+                    "{}:{}: {owner} owns '{}', but has made {ending} touching it",
+                    codeowners_path.display(),
+                    entry.line_number,
+                    entry.pattern
+                )?;
+                num_stale_owners += 1;
+            }
+        }
+    }
+
+    Ok(num_stale_owners)
+}
+
+/// Return the path of the CODEOWNERS file of the repo at `target_dir`,
+/// looking in the same places GitHub does (and in the same order).
+fn find_codeowners(target_dir: &Path) -> eyre::Result<PathBuf> {
+    [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"]
+        .iter()
+        .map(|p| target_dir.join(p))
+        .find(|p| p.is_file())
+        .ok_or_else(|| eyre::eyre!("No CODEOWNERS file found in '{}'", target_dir.display()))
+        .with_suggestion(|| "Pass the path of the file via '--codeowners'")
+}
+
+/// Parse the entries of a CODEOWNERS file, skipping comments and blank lines.
+/// Spaces inside of a pattern can be escaped with a backslash.
+fn parse_codeowners(contents: &str) -> Vec<CodeownersEntry> {
+    let mut entries = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = match line.find(" #") {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut tokens: Vec<String> = Vec::new();
+        let mut token = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => token.extend(chars.next()),
+                c if c.is_whitespace() => {
+                    if !token.is_empty() {
+                        tokens.push(std::mem::take(&mut token));
+                    }
+                }
+                c => token.push(c),
+            }
+        }
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+
+        // Entries without owners only remove the ownership of the matched files
+        let mut tokens = tokens.into_iter();
+        if let Some(pattern) = tokens.next() {
+            entries.push(CodeownersEntry {
+                line_number: i + 1,
+                pattern,
+                owners: tokens.collect(),
+            });
+        }
+    }
+
+    entries
+}
+
+/// Return a regex matching the paths that the CODEOWNERS `pattern` applies to,
+/// which follows (most of) the rules of `.gitignore` files:
+/// - a pattern with a slash at the start or in the middle is relative to the root of the repo,
+///   otherwise it can match at any depth
+/// - a trailing slash only matches directories, while other patterns match both files and
+///   directories (including everything inside of them)
+/// - `*` matches anything but a slash, `**` matches across directories
+/// - `docs/*` only matches the files directly inside `docs`, like on GitHub
+pub fn get_codeowners_regex(pattern: &str) -> eyre::Result<Regex> {
+    let is_directory = pattern.ends_with('/');
+    let trimmed = pattern.trim_end_matches('/');
+    let is_anchored = trimmed.contains('/');
+    let trimmed = trimmed.trim_start_matches('/');
+
+    let mut regex = String::from(if is_anchored { "^" } else { "^(?:.*/)?" });
+    let mut rest = trimmed;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            regex.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            regex.push_str(".*");
+            rest = after;
+        } else {
+            match c {
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    if is_directory {
+        regex.push_str("/.*$");
+    } else if trimmed.ends_with("/*") {
+        regex.push('$');
+    } else {
+        regex.push_str("(?:/.*)?$");
+    }
+
+    Ok(Regex::new(&regex)?)
+}
+
+/// Return true if the author of `commit` is the given CODEOWNERS `owner`,
+/// either by email or by the handle mapped to their name (or email) in `handles`.
+fn is_codeowner(commit: &Commit, owner: &str, handles: &HashMap<String, String>) -> bool {
+    if commit.author_email.eq_ignore_ascii_case(owner) {
+        return true;
+    }
+
+    [&commit.author_name, &commit.author_email]
+        .iter()
+        .filter_map(|identity| handles.get(identity.as_str()))
+        .any(|handle| handle.eq_ignore_ascii_case(owner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_codeowners_skips_comments_and_handles_escapes() {
+        let entries = parse_codeowners(
+            "# Global owners\n\
+             * @org/everyone\n\
+             \n\
+             docs/  @jane @john # the docs team\n\
+             my\\ file.txt @ann\n\
+             /vendor/\n",
+        );
+
+        let entries: Vec<(usize, &str, Vec<&str>)> = entries
+            .iter()
+            .map(|e| {
+                let owners = e.owners.iter().map(String::as_str).collect();
+                (e.line_number, e.pattern.as_str(), owners)
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (2, "*", vec!["@org/everyone"]),
+                (4, "docs/", vec!["@jane", "@john"]),
+                (5, "my file.txt", vec!["@ann"]),
+                (6, "/vendor/", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_codeowners_regex_follows_gitignore_rules() {
+        let matches =
+            |pattern: &str, path: &str| get_codeowners_regex(pattern).unwrap().is_match(path);
+
+        // Unanchored patterns match at any depth, and include what's inside directories
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(matches("build", "tools/build/run.sh"));
+        assert!(!matches("*.rs", "src/main.rsx"));
+
+        // Anchored patterns are relative to the root
+        assert!(matches("/src", "src/lib.rs"));
+        assert!(!matches("/src", "tools/src/lib.rs"));
+        assert!(matches("docs/api", "docs/api/index.md"));
+        assert!(!matches("docs/api", "old/docs/api/index.md"));
+
+        // Trailing slashes only match directories
+        assert!(matches("vendor/", "vendor/lib.c"));
+        assert!(!matches("vendor/", "vendor"));
+
+        // `docs/*` only matches the files directly inside of `docs`
+        assert!(matches("docs/*", "docs/README.md"));
+        assert!(!matches("docs/*", "docs/api/index.md"));
+
+        // `**` matches across directories
+        assert!(matches("**/tests/*.rs", "crates/core/tests/cli.rs"));
+        assert!(matches("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(matches("src/**/mod.rs", "src/mod.rs"));
+
+        // Regex characters are matched literally
+        assert!(matches("a+b.txt", "a+b.txt"));
+        assert!(!matches("a+b.txt", "aab.txt"));
+    }
+}
@@ -0,0 +1,321 @@
+//! The `compare` command: how the commits of each author changed between two time windows
+//! (or two revisions).
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use color_eyre::eyre;
+use serde::Serialize;
+
+use whos_done_that::{get_names_by_email, percentage, Commit};
+
+use crate::output::{
+    display_author, get_author_csv_fields, get_author_csv_header, write_author_table,
+    write_csv_table, write_hidden_entries_message, write_markdown_table, write_markdown_title,
+};
+use crate::{Cli, DateWindow, Format, ReportOptions};
+
+/// How the activity of an author changed from a window to the next one.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum AuthorTrend {
+    /// Only has commits in the second window
+    New,
+    /// Only has commits in the first window
+    Silent,
+    /// Has commits in both windows
+    Active,
+}
+
+/// The commits of an author in each window (or revision) of `compare`.
+#[derive(Serialize)]
+struct AuthorDelta {
+    author_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_email: Option<String>,
+    commits_a: usize,
+    commits_b: usize,
+    commits_change: i64,
+    /// Added plus removed lines
+    lines_a: usize,
+    lines_b: usize,
+    lines_change: i64,
+    /// Shares of the commits of each window, in the 0-100 range
+    percent_commits_a: f64,
+    percent_commits_b: f64,
+    /// In percentage points, which is more meaningful than the raw change
+    /// when the windows don't have the same amount of activity
+    percent_commits_change: f64,
+    trend: AuthorTrend,
+}
+
+/// What the `compare` subcommand compares.
+pub enum CompareSides<'a> {
+    /// The commits of two ranges of author dates
+    Windows {
+        window_a: &'a DateWindow,
+        window_b: &'a DateWindow,
+    },
+    /// The histories of two revisions, where the one of `rev_b` is the analyzed one
+    Revisions {
+        commits_a: Vec<Commit>,
+        rev_a: &'a str,
+        rev_b: &'a str,
+    },
+}
+
+/// The JSON output of the `compare` subcommand.
+#[derive(Serialize)]
+struct CompareReport<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_a: Option<&'a DateWindow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window_b: Option<&'a DateWindow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev_a: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev_b: Option<&'a str>,
+    total_commits_a: usize,
+    total_commits_b: usize,
+    authors: Vec<AuthorDelta>,
+}
+
+/// Count the commits of each author on each side of `sides`, then write how they changed
+/// to `writer`, from the authors ramping up the most to the ones winding down the most.
+/// `commits` are the analyzed ones, which are split by date when comparing time windows.
+/// Return the number of authors that were written.
+pub fn write_compare_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    sides: &CompareSides,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let (commits_a, commits_b): (Vec<&Commit>, Vec<&Commit>) = match sides {
+        CompareSides::Windows { window_a, window_b } => (
+            commits
+                .iter()
+                .filter(|c| window_a.contains(&c.date))
+                .collect(),
+            commits
+                .iter()
+                .filter(|c| window_b.contains(&c.date))
+                .collect(),
+        ),
+        CompareSides::Revisions { commits_a, .. } => {
+            (commits_a.iter().collect(), commits.iter().collect())
+        }
+    };
+    let (noun, label_a, label_b) = match sides {
+        CompareSides::Windows { window_a, window_b } => (
+            ["Window", "window"],
+            window_a.to_string(),
+            window_b.to_string(),
+        ),
+        CompareSides::Revisions { rev_a, rev_b, .. } => (
+            ["Revision", "revision"],
+            rev_a.to_string(),
+            rev_b.to_string(),
+        ),
+    };
+
+    let names_by_email = get_names_by_email(commits);
+    let names_by_email_a = match sides {
+        CompareSides::Revisions { commits_a, .. } => get_names_by_email(commits_a),
+        CompareSides::Windows { .. } => HashMap::new(),
+    };
+    // The commits and the changed lines of each author, on each side
+    let mut counts: HashMap<&str, [(usize, usize); 2]> = HashMap::new();
+    for (side, side_commits) in [&commits_a, &commits_b].into_iter().enumerate() {
+        for commit in side_commits {
+            let changed_lines: usize = commit
+                .file_changes
+                .iter()
+                .map(|f| f.additions + f.removals)
+                .sum();
+            let (num_commits, num_lines) =
+                &mut counts.entry(&commit.author_email).or_default()[side];
+            *num_commits += 1;
+            *num_lines += changed_lines;
+        }
+    }
+    let total_commits_a = commits_a.len();
+    let total_commits_b = commits_b.len();
+
+    let mut authors: Vec<AuthorDelta> = counts
+        .into_iter()
+        .map(|(email, [(commits_a, lines_a), (commits_b, lines_b)])| {
+            let percent_commits_a = percentage(commits_a, total_commits_a);
+            let percent_commits_b = percentage(commits_b, total_commits_b);
+            // Authors that only show up in '--rev-a' aren't part of the analyzed commits
+            let author_name = names_by_email
+                .get(email)
+                .or_else(|| names_by_email_a.get(email))
+                .copied()
+                .unwrap_or(email);
+            AuthorDelta {
+                author_name: author_name.to_string(),
+                author_email: cli.show_emails.then(|| email.to_string()),
+                commits_a,
+                commits_b,
+                commits_change: commits_b as i64 - commits_a as i64,
+                lines_a,
+                lines_b,
+                lines_change: lines_b as i64 - lines_a as i64,
+                percent_commits_a,
+                percent_commits_b,
+                percent_commits_change: ((percent_commits_b - percent_commits_a) * 10.0).round()
+                    / 10.0,
+                trend: match (commits_a, commits_b) {
+                    (0, _) => AuthorTrend::New,
+                    (_, 0) => AuthorTrend::Silent,
+                    _ => AuthorTrend::Active,
+                },
+            }
+        })
+        .collect();
+    authors.sort_by(|a, b| {
+        b.percent_commits_change
+            .total_cmp(&a.percent_commits_change)
+            .then_with(|| a.author_name.cmp(&b.author_name))
+    });
+    if cli.reverse {
+        authors.reverse();
+    }
+
+    let num_authors = authors.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        authors.truncate(top);
+    }
+    let num_hidden_authors = num_authors - authors.len();
+
+    let header = [
+        "Author",
+        "Commits A",
+        "Commits B",
+        "Change",
+        "% Commits A",
+        "% Commits B",
+        "% Change",
+        "Lines A",
+        "Lines B",
+        "Lines change",
+        "Trend",
+    ];
+    let trend_name = |trend: AuthorTrend| match trend {
+        AuthorTrend::New => "new",
+        AuthorTrend::Silent => "silent",
+        AuthorTrend::Active => "active",
+    };
+    let get_stats = |a: &AuthorDelta| {
+        vec![
+            a.commits_a.to_string(),
+            a.commits_b.to_string(),
+            format!("{:+}", a.commits_change),
+            format!("{:.1}", a.percent_commits_a),
+            format!("{:.1}", a.percent_commits_b),
+            format!("{:+.1}", a.percent_commits_change),
+            a.lines_a.to_string(),
+            a.lines_b.to_string(),
+            format!("{:+}", a.lines_change),
+            trend_name(a.trend).to_string(),
+        ]
+    };
+    let rows: Vec<Vec<String>> = authors
+        .iter()
+        .map(|a| {
+            let mut row = vec![display_author(&a.author_name, a.author_email.as_deref())];
+            row.extend(get_stats(a));
+            row
+        })
+        .collect();
+
+    match options.format {
+        Format::Text => {
+            writeln!(
+                writer,
+                "{} A ({label_a}) has {total_commits_a} commits, {} B ({label_b}) has {total_commits_b}",
+                noun[0], noun[1]
+            )?;
+            for a in &authors {
+                let trend = match a.trend {
+                    AuthorTrend::New => format!(", new in {} B", noun[1]),
+                    AuthorTrend::Silent => format!(", silent in {} B", noun[1]),
+                    AuthorTrend::Active => String::new(),
+                };
+                writeln!(
+                    writer,
+                    "{}: {} → {} commits ({:+}), {:.1}% → {:.1}% of the commits ({:+.1} points), {} → {} lines changed ({:+}){trend}",
+                    options.palette.author(&display_author(&a.author_name, a.author_email.as_deref())),
+                    a.commits_a,
+                    a.commits_b,
+                    a.commits_change,
+                    a.percent_commits_a,
+                    a.percent_commits_b,
+                    a.percent_commits_change,
+                    a.lines_a,
+                    a.lines_b,
+                    a.lines_change
+                )?;
+            }
+        }
+        Format::Table => write_author_table(writer, &header, rows, options.palette)?,
+        Format::Json => {
+            let (window_a, window_b, rev_a, rev_b) = match sides {
+                CompareSides::Windows { window_a, window_b } => {
+                    (Some(*window_a), Some(*window_b), None, None)
+                }
+                CompareSides::Revisions { rev_a, rev_b, .. } => {
+                    (None, None, Some(*rev_a), Some(*rev_b))
+                }
+            };
+            let report = CompareReport {
+                window_a,
+                window_b,
+                rev_a,
+                rev_b,
+                total_commits_a,
+                total_commits_b,
+                authors,
+            };
+            serde_json::to_writer_pretty(&mut *writer, &report)?;
+            writeln!(writer)?;
+            return Ok(report.authors.len());
+        }
+        Format::Csv => {
+            let show_emails = authors.iter().any(|a| a.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.extend(
+                [
+                    "commits_a",
+                    "commits_b",
+                    "commits_change",
+                    "percent_commits_a",
+                    "percent_commits_b",
+                    "percent_commits_change",
+                    "lines_a",
+                    "lines_b",
+                    "lines_change",
+                    "trend",
+                ]
+                .map(String::from),
+            );
+            let records = authors.iter().map(|a| {
+                let mut record =
+                    get_author_csv_fields(&a.author_name, a.author_email.as_deref(), show_emails);
+                record.extend(get_stats(a));
+                record
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            // The trend is a word, so it's aligned like the authors
+            write_markdown_table(writer, &header, &rows, &[0, header.len() - 1])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(authors.len())
+}
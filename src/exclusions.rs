@@ -0,0 +1,81 @@
+//! The paths left out of the analysis: `--exclude`, `--exclude-from` and `--exclude-generated`.
+
+use std::path::Path;
+
+use color_eyre::{eyre, eyre::WrapErr};
+use regex::Regex;
+
+use crate::codeowners::get_codeowners_regex;
+
+/// A pattern passed to `--exclude`, or read from `--exclude-from`.
+pub struct PathPattern {
+    regex: Regex,
+    /// Patterns starting with a '!' include the matching paths again
+    is_negated: bool,
+}
+
+/// Patterns matching the files that are usually generated, rather than written by hand.
+const GENERATED_PATTERNS: [&str; 12] = [
+    "*.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "node_modules/",
+    "vendor/",
+    "*.min.js",
+    "*.min.css",
+    "*.js.map",
+    "*.pb.go",
+    "*_pb2.py",
+    "*.snap",
+];
+
+/// Compile the patterns passed to `--exclude`, plus the ones read from `exclude_from`
+/// and the ones matching generated files if `exclude_generated` is set.
+/// Like in `.gitignore` files, empty lines and lines starting with '#' are skipped.
+pub fn get_excluded_paths(
+    patterns: &[String],
+    exclude_from: Option<&Path>,
+    exclude_generated: bool,
+) -> eyre::Result<Vec<PathPattern>> {
+    // The generated files come first, so that they can be included again via '!'
+    let mut patterns: Vec<String> = GENERATED_PATTERNS
+        .iter()
+        .filter(|_| exclude_generated)
+        .map(|p| p.to_string())
+        .chain(patterns.iter().cloned())
+        .collect();
+    if let Some(path) = exclude_from {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read '{}'", path.display()))?;
+        patterns.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+
+    patterns
+        .iter()
+        .map(|pattern| {
+            let (is_negated, glob) = match pattern.strip_prefix('!') {
+                Some(glob) => (true, glob),
+                None => (false, pattern.as_str()),
+            };
+            let regex = get_codeowners_regex(glob)
+                .wrap_err_with(|| format!("'{pattern}' is not a valid pattern"))?;
+            Ok(PathPattern { regex, is_negated })
+        })
+        .collect()
+}
+
+/// Return true if `path` is matched by `patterns`, where the last matching pattern wins.
+pub fn is_path_excluded(path: &str, patterns: &[PathPattern]) -> bool {
+    patterns
+        .iter()
+        .rev()
+        .find(|p| p.regex.is_match(path))
+        .is_some_and(|p| !p.is_negated)
+}
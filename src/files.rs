@@ -0,0 +1,336 @@
+//! The reports about the files: `--group-by file`, `--group-by directory` and `--orphan-risk`.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use color_eyre::eyre;
+use owo_colors::Style;
+use serde::Serialize;
+
+use whos_done_that::{aggregate_by_path, Commit, FileData};
+
+use crate::output::{
+    display_author, get_author_csv_fields, get_author_csv_header, truncate_with_ellipsis,
+    write_aligned_table, write_csv_table, write_hidden_entries_message, write_json_report,
+    write_markdown_table, write_markdown_title, Palette, MAX_TABLE_AUTHOR_WIDTH,
+};
+use crate::{Cli, Format, ReportOptions};
+
+/// The files that were only ever changed by a single author, see `--orphan-risk`.
+#[derive(Serialize)]
+struct OrphanFiles {
+    author_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author_email: Option<String>,
+    paths: Vec<String>,
+}
+
+/// Find the files of `commits` that were only ever changed by a single author,
+/// then write them to `writer` grouped by that author, sorted by name.
+/// Return the number of files that were written.
+pub fn write_orphan_risk_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    // Two authors are enough to tell whether a file has a single one
+    let files_data = aggregate_by_path(commits, 2, |path| path.to_string());
+    let mut paths_by_email: HashMap<String, OrphanFiles> = HashMap::new();
+    for file_data in files_data {
+        let [author] = file_data.authors.as_slice() else {
+            continue;
+        };
+        let email = author.author_email.clone().unwrap_or_default();
+        paths_by_email
+            .entry(email)
+            .or_insert_with(|| OrphanFiles {
+                author_name: author.author_name.clone(),
+                author_email: author.author_email.clone(),
+                paths: Vec::new(),
+            })
+            .paths
+            .push(file_data.path);
+    }
+
+    let mut orphans: Vec<OrphanFiles> = paths_by_email.into_values().collect();
+    orphans.iter_mut().for_each(|o| o.paths.sort());
+    orphans
+        .sort_by(|a, b| (&a.author_name, &a.author_email).cmp(&(&b.author_name, &b.author_email)));
+    if !cli.show_emails {
+        orphans.iter_mut().for_each(|o| o.author_email = None);
+    }
+    if cli.reverse {
+        orphans.reverse();
+    }
+
+    let num_authors = orphans.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        orphans.truncate(top);
+    }
+    let num_hidden_authors = num_authors - orphans.len();
+
+    match options.format {
+        Format::Text | Format::Table => {
+            for o in &orphans {
+                let files = match o.paths.len() {
+                    1 => "1 file".to_string(),
+                    n => format!("{n} files"),
+                };
+                writeln!(
+                    writer,
+                    "{} is the only author of {files}:",
+                    options
+                        .palette
+                        .author(&display_author(&o.author_name, o.author_email.as_deref()))
+                )?;
+                for path in &o.paths {
+                    writeln!(writer, "  {path}")?;
+                }
+            }
+        }
+        Format::Json => write_json_report(writer, &orphans)?,
+        Format::Csv => {
+            let show_emails = orphans.iter().any(|o| o.author_email.is_some());
+            let mut header = get_author_csv_header(show_emails);
+            header.push("path".to_string());
+            let records = orphans.iter().flat_map(|o| {
+                o.paths.iter().map(move |path| {
+                    let mut record = get_author_csv_fields(
+                        &o.author_name,
+                        o.author_email.as_deref(),
+                        show_emails,
+                    );
+                    record.push(path.clone());
+                    record
+                })
+            });
+            write_csv_table(writer, options.delimiter, header, records)?;
+        }
+        Format::Markdown => {
+            write_markdown_title(writer, options.title.as_deref())?;
+            let rows: Vec<Vec<String>> = orphans
+                .iter()
+                .flat_map(|o| {
+                    let author = display_author(&o.author_name, o.author_email.as_deref());
+                    o.paths
+                        .iter()
+                        .map(move |path| vec![author.clone(), path.clone()])
+                })
+                .collect();
+            write_markdown_table(writer, &["Author", "Path"], &rows, &[0, 1])?;
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_authors, "authors")?;
+
+    Ok(orphans.iter().map(|o| o.paths.len()).sum())
+}
+
+/// Compute the stats of each file from `commits`, then write them to `writer`,
+/// along with the authors that contributed the most to each file.
+/// Return the number of files that were written.
+pub fn write_file_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let files_data = aggregate_by_path(commits, cli.authors_per_file, |path| path.to_string());
+    write_paths_report(writer, cli, options, files_data, "files")
+}
+
+/// Compute the stats of each directory from `commits`, grouping files by their
+/// first `--depth` path components, then write them to `writer` along with
+/// the authors that contributed the most to each directory.
+/// Return the number of directories that were written.
+pub fn write_directory_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    commits: &[Commit],
+) -> eyre::Result<usize> {
+    let directories_data = aggregate_by_path(commits, cli.authors_per_file, |path| {
+        get_parent_directory(path, cli.depth)
+    });
+    write_paths_report(writer, cli, options, directories_data, "directories")
+}
+
+/// Write the stats of each path to `writer`, according to the filters requested in `cli`.
+/// `entries_name` is used to refer to the paths when some of them are hidden.
+/// Return the number of paths that were written.
+fn write_paths_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    options: &ReportOptions,
+    mut files_data: Vec<FileData>,
+    entries_name: &str,
+) -> eyre::Result<usize> {
+    if !cli.show_emails {
+        files_data
+            .iter_mut()
+            .flat_map(|f| f.authors.iter_mut())
+            .for_each(|a| a.author_email = None);
+    }
+
+    let num_files = files_data.len();
+    if let Some(top) = cli.top.filter(|n| *n > 0) {
+        files_data.truncate(top);
+    }
+    let num_hidden_files = num_files - files_data.len();
+
+    match options.format {
+        Format::Text => write_files_text_report(writer, &files_data, options.palette)?,
+        Format::Table => write_files_table_report(writer, &files_data, options.palette)?,
+        Format::Json => write_json_report(writer, &files_data)?,
+        Format::Csv => write_files_csv_report(writer, &files_data, options.delimiter)?,
+        Format::Markdown => {
+            write_files_markdown_report(writer, &files_data, options.title.as_deref())?
+        }
+    }
+
+    write_hidden_entries_message(writer, options.format, num_hidden_files, entries_name)?;
+
+    Ok(files_data.len())
+}
+
+/// Return the first `depth` components of the directory containing the file at `path`.
+/// Files at the root of the repo (or a `depth` of 0) end up in `.`.
+pub fn get_parent_directory(path: &str, depth: usize) -> String {
+    let mut components: Vec<&str> = path.split('/').collect();
+    // The last component is the file name
+    components.pop();
+    components.truncate(depth);
+
+    if components.is_empty() {
+        ".".to_string()
+    } else {
+        components.join("/")
+    }
+}
+
+/// Write one human readable line per file (or directory) to `writer`, listing its top authors.
+fn write_files_text_report(
+    writer: &mut impl Write,
+    files_data: &[FileData],
+    palette: Palette,
+) -> eyre::Result<()> {
+    for file_data in files_data {
+        let authors = file_data
+            .authors
+            .iter()
+            .map(|a| {
+                format!(
+                    "{} ({:.1}%, {})",
+                    palette.author(&a.display_name()),
+                    a.percent_changed_lines,
+                    palette.code_edits(&a.code_edits)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(
+            writer,
+            "{}: {}, mostly by {authors}",
+            file_data.path,
+            palette.code_edits(&file_data.code_edits)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write `files_data` to `writer` as a table with aligned columns,
+/// with one row for each of the top authors of each file.
+fn write_files_table_report(
+    writer: &mut impl Write,
+    files_data: &[FileData],
+    palette: Palette,
+) -> eyre::Result<()> {
+    let header = ["Path", "Author", "Additions", "Removals", "% Lines"];
+    let mut rows: Vec<Vec<String>> = Vec::new();
+
+    for file_data in files_data {
+        for (i, author) in file_data.authors.iter().enumerate() {
+            // Only mention the file once, so that it's easier to see where each file starts
+            let path = if i == 0 { file_data.path.as_str() } else { "" };
+            rows.push(vec![
+                path.to_string(),
+                truncate_with_ellipsis(&author.display_name(), MAX_TABLE_AUTHOR_WIDTH),
+                author.code_edits.additions.to_string(),
+                author.code_edits.removals.to_string(),
+                format!("{:.1}", author.percent_changed_lines),
+            ]);
+        }
+    }
+
+    let styles = [
+        Style::new(),
+        Style::new().bold(),
+        Style::new().green(),
+        Style::new().red(),
+    ];
+    write_aligned_table(writer, &header, &rows, 2, &styles, palette)
+}
+
+/// Write `files_data` to `writer` as CSV, one row for each of the top authors of each file,
+/// using `delimiter` to separate the fields.
+fn write_files_csv_report(
+    writer: &mut impl Write,
+    files_data: &[FileData],
+    delimiter: u8,
+) -> eyre::Result<()> {
+    let show_emails = files_data
+        .iter()
+        .flat_map(|f| &f.authors)
+        .any(|a| a.author_email.is_some());
+
+    let mut header = vec!["path".to_string()];
+    header.extend(get_author_csv_header(show_emails));
+    header.extend(["additions", "removals", "percent_changed_lines"].map(String::from));
+    let records = files_data.iter().flat_map(|file_data| {
+        file_data.authors.iter().map(move |author| {
+            let mut record = vec![file_data.path.clone()];
+            record.extend(get_author_csv_fields(
+                &author.author_name,
+                author.author_email.as_deref(),
+                show_emails,
+            ));
+            record.extend([
+                author.code_edits.additions.to_string(),
+                author.code_edits.removals.to_string(),
+                format!("{:.1}", author.percent_changed_lines),
+            ]);
+            record
+        })
+    });
+    write_csv_table(writer, delimiter, header, records)
+}
+
+/// Write `files_data` to `writer` as a GitHub flavored markdown table,
+/// with one row for each of the top authors of each file.
+/// If a `title` is given, it's written as a heading before the table.
+fn write_files_markdown_report(
+    writer: &mut impl Write,
+    files_data: &[FileData],
+    title: Option<&str>,
+) -> eyre::Result<()> {
+    write_markdown_title(writer, title)?;
+    let rows: Vec<Vec<String>> = files_data
+        .iter()
+        .flat_map(|file_data| {
+            file_data.authors.iter().map(|author| {
+                vec![
+                    file_data.path.clone(),
+                    author.display_name(),
+                    author.code_edits.additions.to_string(),
+                    author.code_edits.removals.to_string(),
+                    format!("{:.1}", author.percent_changed_lines),
+                ]
+            })
+        })
+        .collect();
+    let header = ["Path", "Author", "Additions", "Removals", "% Lines"];
+    write_markdown_table(writer, &header, &rows, &[0, 1])
+}
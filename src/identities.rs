@@ -0,0 +1,207 @@
+//! Who the commits are credited to, instead of their authors: the teams of `--teams`,
+//! the organizations of `--group-by domain` and the pseudonyms of `--anonymize`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use color_eyre::{eyre, eyre::WrapErr, Section};
+use regex::Regex;
+
+use whos_done_that::{aggregate_by_author, Commit};
+
+use crate::authors::sort_authors;
+use crate::SortBy;
+
+/// Read the TOML (or JSON, for `.json` files) file at `path`, mapping strings to strings.
+/// `description` names the file in the errors, and `suggestion` shows how it should look.
+fn read_mapping_file(
+    path: &Path,
+    description: &str,
+    suggestion: &'static str,
+) -> eyre::Result<HashMap<String, String>> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read the {description} at '{}'", path.display()))?;
+
+    let mapping = match path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+    {
+        true => serde_json::from_str(&contents).map_err(eyre::Report::new),
+        false => toml::from_str(&contents).map_err(eyre::Report::new),
+    };
+    mapping
+        .wrap_err_with(|| format!("Failed to parse the {description} at '{}'", path.display()))
+        .with_suggestion(|| suggestion)
+}
+
+/// The team of the authors that aren't listed in the file passed to `--teams`.
+const UNASSIGNED_TEAM: &str = "Unassigned";
+
+/// Read the TOML (or JSON) file at `path`, mapping author names (or emails) to team names.
+pub fn read_teams(path: &Path) -> eyre::Result<Teams> {
+    let teams = read_mapping_file(
+        path,
+        "teams",
+        "Each author should be mapped to a team, e.g. '\"Jane Doe\" = \"Platform\"'",
+    )?;
+
+    let (globs, by_identity): (HashMap<_, _>, HashMap<_, _>) = teams
+        .into_iter()
+        .partition(|(identity, _)| is_email_glob(identity));
+    // The longest globs are usually the most specific ones, so they're tried first
+    let mut globs: Vec<(String, String)> = globs.into_iter().collect();
+    globs.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    let email_globs = globs
+        .into_iter()
+        .map(|(glob, team)| Ok((get_email_glob_regex(&glob)?, team)))
+        .collect::<eyre::Result<_>>()?;
+
+    Ok(Teams {
+        by_identity,
+        email_globs,
+    })
+}
+
+/// The teams read from the file passed to `--teams`.
+pub struct Teams {
+    /// The team of each author name (or email)
+    by_identity: HashMap<String, String>,
+    /// The team of the emails matching each glob, e.g. `*@corp.com`
+    email_globs: Vec<(Regex, String)>,
+}
+
+impl Teams {
+    /// Return the team of the author with the given name and email, if any.
+    /// Exact names and emails win over globs.
+    fn get(&self, author_name: &str, author_email: &str) -> Option<&str> {
+        self.by_identity
+            .get(author_name)
+            .or_else(|| self.by_identity.get(author_email))
+            .or_else(|| {
+                self.email_globs
+                    .iter()
+                    .find(|(regex, _)| regex.is_match(author_email))
+                    .map(|(_, team)| team)
+            })
+            .map(String::as_str)
+    }
+}
+
+/// Whether `identity`, a key of the teams file, is a glob of emails like `*@corp.com`.
+/// Only emails can be globs, so that names like 'Who?' are still matched exactly.
+fn is_email_glob(identity: &str) -> bool {
+    identity.contains('@') && identity.contains(['*', '?'])
+}
+
+/// Return the regex matching the emails matched by `glob`, where `*` matches
+/// any number of characters and `?` a single one. Emails are case insensitive.
+fn get_email_glob_regex(glob: &str) -> eyre::Result<Regex> {
+    let pattern: String = glob
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect();
+    Regex::new(&format!("(?i)^{pattern}$"))
+        .wrap_err_with(|| format!("'{glob}' is not a valid email glob"))
+}
+
+/// Credit each of `commits` to the team of its author, by name or by email.
+/// The team name is also used as the email, so that the teams are aggregated like authors.
+/// The authors without a team are listed, so that they can be added to the file.
+pub fn assign_teams(commits: &mut [Commit], teams: &Teams) {
+    let mut unassigned_authors = Vec::new();
+    for commit in commits {
+        let team = match teams.get(&commit.author_name, &commit.author_email) {
+            Some(team) => team,
+            None => {
+                let author = format!("{} <{}>", commit.author_name, commit.author_email);
+                if !unassigned_authors.contains(&author) {
+                    unassigned_authors.push(author);
+                }
+                UNASSIGNED_TEAM
+            }
+        };
+        commit.author_name = team.to_string();
+        commit.author_email = team.to_string();
+    }
+
+    if !unassigned_authors.is_empty() {
+        unassigned_authors.sort();
+        tracing::warn!(
+            "{} authors aren't in any team, so they're reported under '{UNASSIGNED_TEAM}': {}",
+            unassigned_authors.len(),
+            unassigned_authors.join(", ")
+        );
+    }
+}
+
+/// The organization of the authors whose email doesn't have a domain, see `--group-by domain`.
+const UNKNOWN_DOMAIN: &str = "unknown";
+
+/// Read the TOML (or JSON) file at `path`, mapping email domains to organization names.
+pub fn read_domain_map(path: &Path) -> eyre::Result<HashMap<String, String>> {
+    let domain_map = read_mapping_file(
+        path,
+        "domain map",
+        "Each domain should be mapped to an organization, e.g. '\"gmail.com\" = \"Individuals\"'",
+    )?;
+    // Domains are case insensitive, so they're matched in lowercase
+    Ok(domain_map
+        .into_iter()
+        .map(|(domain, name)| (domain.to_lowercase(), name))
+        .collect())
+}
+
+/// Credit each of `commits` to the email domain of its author, or to the organization
+/// it's mapped to in `domain_map`. Like teams, the name of the domain is also used as
+/// the email, so that the domains are aggregated like authors.
+pub fn assign_domains(commits: &mut [Commit], domain_map: &HashMap<String, String>) {
+    for commit in commits {
+        let domain = commit
+            .author_email
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.trim().to_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .unwrap_or_else(|| UNKNOWN_DOMAIN.to_string());
+        let organization = domain_map.get(&domain).cloned().unwrap_or(domain);
+        commit.author_name = organization.clone();
+        commit.author_email = organization;
+    }
+}
+
+/// Replace the identity of each author of `commits` with a pseudonym like `author-001`,
+/// numbered from the author with the most commits. Ties are broken by name and email,
+/// so that the same commits always get the same pseudonyms.
+pub fn anonymize_authors(commits: &mut [Commit]) {
+    let mut authors_data = aggregate_by_author(commits);
+    sort_authors(&mut authors_data, SortBy::Commits);
+    let pseudonyms: HashMap<String, String> = authors_data
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, a)| Some((a.author_email?, format!("author-{:03}", i + 1))))
+        .collect();
+
+    for commit in commits {
+        let pseudonym = &pseudonyms[&commit.author_email];
+        commit.author_email = format!("{pseudonym}@redacted");
+        commit.author_name = pseudonym.clone();
+        commit.co_authors.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_emails_are_globs_in_the_teams() {
+        assert!(is_email_glob("*@corp.com"));
+        assert!(is_email_glob("jane?@example.com"));
+        assert!(!is_email_glob("jane@example.com"));
+        assert!(!is_email_glob("Who?"));
+        assert!(!is_email_glob("*NSYNC"));
+    }
+}
@@ -0,0 +1,1748 @@
+//! Find out who wrote the code of a git repository, by going through its history.
+//!
+//! The history is read by running `git`, which must be available in the `PATH`.
+//! [`analyze`] covers the common case, while [`get_commits`] and the `aggregate_by_*`
+//! functions can be used to build different reports out of the same commits.
+//! [`get_blame_data`] counts the lines of the current files that each author touched last instead.
+//! The [`Repo`] trait asks for the basic stats of the authors without tying the callers
+//! to `git`, which [`GitRepo`] runs.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// The ways the functions of this crate can fail.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("'{}' is not a git repository", .0.display())]
+    NotAGitRepo(PathBuf),
+    #[error("The revision '{0}' doesn't exist")]
+    UnknownRevision(String),
+    #[error("Failed to run git, make sure that it's installed and in the PATH")]
+    GitNotFound(#[source] std::io::Error),
+    /// git exited with non 0, `stderr` is what it printed
+    #[error("'{command}' failed: {stderr}")]
+    GitFailed { command: String, stderr: String },
+    #[error("Failed to parse the output of '{command}'")]
+    InvalidGitOutput { command: String },
+    #[error("'{pattern}' is not a valid regular expression")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Return the stats of each author of the repo living at `target_dir`, sorted by name.
+/// Authors without any commit matching `options` are not reported.
+pub fn analyze(target_dir: &Path, options: &QueryOptions) -> Result<Vec<AuthorData>> {
+    ensure_is_git_repo(target_dir)?;
+    let commits = get_commits(target_dir, options, |_| {})?;
+
+    let mut authors_data = aggregate_by_author(&commits);
+    compute_percentages(&mut authors_data);
+
+    Ok(authors_data)
+}
+
+/// The queries to tell who wrote the code of a repo, whatever reads its history.
+pub trait Repo {
+    /// Return the name and email of each author, sorted by name.
+    fn authors(&self) -> Result<Vec<(String, String)>>;
+    /// Return the number of commits of the author with `author_email`, 0 if unknown.
+    fn commit_count(&self, author_email: &str) -> Result<usize>;
+    /// Return the lines added and removed by the author with `author_email`.
+    fn edits(&self, author_email: &str) -> Result<CodeEdits>;
+}
+
+/// A [`Repo`] whose history is read by running `git`, once, when it's opened.
+#[derive(Clone, Debug)]
+pub struct GitRepo {
+    authors_data: Vec<AuthorData>,
+}
+
+impl GitRepo {
+    /// Read the commits matching `options` of the repo living at `target_dir`.
+    pub fn open(target_dir: &Path, options: &QueryOptions) -> Result<Self> {
+        Ok(Self {
+            authors_data: analyze(target_dir, options)?,
+        })
+    }
+
+    fn get_author(&self, author_email: &str) -> Option<&AuthorData> {
+        self.authors_data
+            .iter()
+            .find(|a| a.author_email.as_deref() == Some(author_email))
+    }
+}
+
+impl Repo for GitRepo {
+    fn authors(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .authors_data
+            .iter()
+            .map(|a| {
+                let email = a.author_email.clone().unwrap_or_default();
+                (a.author_name.clone(), email)
+            })
+            .collect())
+    }
+
+    fn commit_count(&self, author_email: &str) -> Result<usize> {
+        Ok(self.get_author(author_email).map_or(0, |a| a.num_commits))
+    }
+
+    fn edits(&self, author_email: &str) -> Result<CodeEdits> {
+        Ok(self
+            .get_author(author_email)
+            .map_or_else(CodeEdits::default, |a| a.code_edits))
+    }
+}
+
+/// Options that control which commits (and identities) are analyzed.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryOptions {
+    /// The branch (or any other revision) to analyze, `HEAD` if empty
+    pub branch_name: String,
+    pub all_branches: bool,
+    pub include_merges: bool,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub paths: Vec<String>,
+    pub use_mailmap: bool,
+    /// Skip the files marked as `linguist-vendored` or `linguist-generated`
+    pub respect_gitattributes: bool,
+    /// Ignore the changes that only touch whitespace
+    pub ignore_whitespace: bool,
+    /// Detect renamed files, so that their lines stay credited to their authors
+    pub follow_renames: bool,
+    /// Maximum number of files blamed in parallel, 0 to use the number of CPUs.
+    /// It doesn't change the results, so it's left out of the cache keys.
+    #[serde(skip)]
+    pub jobs: usize,
+}
+
+impl QueryOptions {
+    /// The revisions to pass to `git log`.
+    pub fn revisions(&self) -> &str {
+        if self.all_branches {
+            "--all"
+        } else if self.branch_name.is_empty() {
+            "HEAD"
+        } else {
+            &self.branch_name
+        }
+    }
+
+    /// A human readable description of the analyzed revisions, for report titles.
+    pub fn describe_revisions(&self) -> &str {
+        if self.all_branches {
+            "all branches"
+        } else {
+            &self.branch_name
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CodeEdits {
+    pub additions: usize,
+    pub removals: usize,
+    /// Number of changes to binary files, which don't have any line to count
+    pub binary_changes: usize,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AuthorData {
+    pub author_name: String,
+    /// Only reported when requested, see `--show-emails`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_email: Option<String>,
+    pub num_commits: usize,
+    #[serde(flatten)]
+    pub code_edits: CodeEdits,
+    /// Share of all of the commits of the repo, in the 0-100 range
+    pub percent_commits: f64,
+    /// Share of all of the added lines of the repo, in the 0-100 range
+    pub percent_additions: f64,
+    /// Author date of the oldest commit, as RFC 3339 (see [`Commit::datetime`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_commit: Option<String>,
+    /// Author date of the most recent commit, as RFC 3339 (see [`Commit::datetime`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<String>,
+    /// Author date of the most recent commit, as seconds since the Unix epoch.
+    /// Unlike `last_commit`, it can be compared across timezones
+    #[serde(skip)]
+    pub last_commit_timestamp: i64,
+    /// Number of distinct days with at least one commit
+    pub active_days: usize,
+    /// Number of days from the first to the last commit, 0 if they were made on the same day
+    pub active_span_days: usize,
+    /// Added plus removed lines of an average commit, 0 without any commit
+    pub avg_lines_per_commit: f64,
+    /// Added plus removed lines of the biggest commit
+    pub largest_commit_lines: usize,
+    /// Number of the commits credited via a `Co-authored-by` trailer,
+    /// only computed with `--credit-coauthors`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coauthored_commits: Option<usize>,
+    /// Number of commits made on each day of the week, from Monday to Sunday
+    #[serde(skip)]
+    pub commits_per_weekday: [usize; 7],
+    /// Number of commits made in each hour of the day, from 00:00 to 23:00
+    #[serde(skip)]
+    pub commits_per_hour: [usize; 24],
+    /// Recency weighted number of commits, only computed with `--half-life`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+/// The contributions of a single author to a file (or directory).
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileAuthorData {
+    pub author_name: String,
+    /// Only reported when requested, see `--show-emails`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_email: Option<String>,
+    #[serde(flatten)]
+    pub code_edits: CodeEdits,
+    /// Share of the lines changed in the file, in the 0-100 range
+    pub percent_changed_lines: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileData {
+    pub path: String,
+    #[serde(flatten)]
+    pub code_edits: CodeEdits,
+    /// The authors that changed the most lines, in descending order
+    pub authors: Vec<FileAuthorData>,
+}
+
+/// The lines that an author touched last, according to `git blame`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BlameData {
+    pub author_name: String,
+    /// Only reported when requested, see `--show-emails`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_email: Option<String>,
+    pub lines_owned: usize,
+    /// Share of all of the lines of the analyzed files, in the 0-100 range
+    pub percent_lines: f64,
+}
+
+/// A single commit, as parsed from `git log --numstat`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub author_name: String,
+    pub author_email: String,
+    /// The author date, as seconds since the Unix epoch
+    pub timestamp: i64,
+    /// The author date, as YYYY-MM-DD in the timezone of the author
+    pub date: String,
+    /// The author date, as RFC 3339 in the timezone of the author, e.g. `2024-01-01T12:00:00+01:00`
+    pub datetime: String,
+    /// The day of the week of `date`, from 0 (Monday) to 6 (Sunday)
+    pub weekday: usize,
+    /// The hour of the author date, from 0 to 23 in the timezone of the author
+    pub hour: usize,
+    /// Name and email of the people listed in the `Co-authored-by` trailers
+    pub co_authors: Vec<(String, String)>,
+    /// Whether this is the copy of a commit given to one of its co-authors,
+    /// see [`credit_coauthors`]
+    pub is_coauthored: bool,
+    pub file_changes: Vec<FileChange>,
+}
+
+/// The lines changed by a commit in a single file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: String,
+    /// The previous path of the file, if the commit renamed it
+    pub renamed_from: Option<String>,
+    pub additions: usize,
+    pub removals: usize,
+    /// Binary files don't have any line, so `additions` and `removals` are always 0
+    pub is_binary: bool,
+}
+
+impl CodeEdits {
+    /// Return the number of added lines minus the number of removed lines.
+    pub fn net_lines(&self) -> i64 {
+        self.additions as i64 - self.removals as i64
+    }
+
+    /// Return the total number of lines touched, both added and removed.
+    pub fn changed_lines(&self) -> usize {
+        self.additions + self.removals
+    }
+
+    /// Count the lines (or the binary file) changed by `file_change`.
+    pub fn add(&mut self, file_change: &FileChange) {
+        self.additions += file_change.additions;
+        self.removals += file_change.removals;
+        if file_change.is_binary {
+            self.binary_changes += 1;
+        }
+    }
+
+    /// Describe the number of binary changes, e.g. `, 3 binary files changed`,
+    /// or return an empty string if there are none.
+    pub fn binary_changes_suffix(&self) -> String {
+        match self.binary_changes {
+            0 => String::new(),
+            1 => ", 1 binary file changed".to_string(),
+            n => format!(", {n} binary files changed"),
+        }
+    }
+}
+
+impl AuthorData {
+    /// Return the data of an author that hasn't made any commit yet.
+    pub fn new(author_name: &str, author_email: &str) -> Self {
+        Self {
+            author_name: author_name.to_string(),
+            author_email: Some(author_email.to_string()),
+            num_commits: 0,
+            code_edits: CodeEdits::default(),
+            percent_commits: 0.0,
+            percent_additions: 0.0,
+            first_commit: None,
+            last_commit: None,
+            last_commit_timestamp: 0,
+            active_days: 0,
+            active_span_days: 0,
+            avg_lines_per_commit: 0.0,
+            largest_commit_lines: 0,
+            coauthored_commits: None,
+            commits_per_weekday: [0; 7],
+            commits_per_hour: [0; 24],
+            score: None,
+        }
+    }
+
+    /// Return the name of the author, followed by the email if it's available.
+    pub fn display_name(&self) -> String {
+        format_display_name(&self.author_name, self.author_email.as_deref())
+    }
+
+    /// Return the number of added lines minus the number of removed lines.
+    pub fn net_lines(&self) -> i64 {
+        self.code_edits.net_lines()
+    }
+
+    /// Return the date of `first_commit`, as YYYY-MM-DD in the timezone of the author.
+    pub fn first_commit_date(&self) -> Option<&str> {
+        self.first_commit.as_deref().map(get_date)
+    }
+
+    /// Return the date of `last_commit`, as YYYY-MM-DD in the timezone of the author.
+    pub fn last_commit_date(&self) -> Option<&str> {
+        self.last_commit.as_deref().map(get_date)
+    }
+}
+
+/// Return the YYYY-MM-DD date at the start of the RFC 3339 `datetime`.
+fn get_date(datetime: &str) -> &str {
+    datetime.get(..10).unwrap_or(datetime)
+}
+
+impl BlameData {
+    /// Return the name of the author, followed by the email if it's available.
+    pub fn display_name(&self) -> String {
+        format_display_name(&self.author_name, self.author_email.as_deref())
+    }
+}
+
+impl FileAuthorData {
+    /// Return the name of the author, followed by the email if it's available.
+    pub fn display_name(&self) -> String {
+        format_display_name(&self.author_name, self.author_email.as_deref())
+    }
+}
+
+/// Return `name`, followed by `email` if it's available.
+fn format_display_name(name: &str, email: Option<&str>) -> String {
+    match email {
+        Some(email) => format!("{name} <{email}>"),
+        None => name.to_string(),
+    }
+}
+
+impl Display for CodeEdits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = format!(
+            "{} additions and {} removals{}",
+            self.additions,
+            self.removals,
+            self.binary_changes_suffix()
+        );
+        write!(f, "{s}")
+    }
+}
+
+/// Patterns matching the names or emails used by the most common bots.
+pub const BOT_PATTERNS: [&str; 5] = [
+    r"\[bot\]$",
+    r"\[bot\]@users\.noreply\.github\.com$",
+    r"-bot@",
+    // e.g. 'Renovate Bot' or 'ci-bot'
+    r"(?i)[\s-]bot$",
+    // Bots that sometimes commit without the '[bot]' suffix
+    r"(?i)^(dependabot|renovate|github-actions|greenkeeper|snyk-bot|pre-commit-ci)\b",
+];
+
+/// Compile the patterns passed to `--exclude-author`,
+/// plus the ones matching bots if `exclude_bots` is set.
+/// Patterns match whole words of the name or email, ignoring case,
+/// unless they are wrapped in slashes (e.g. `/^ci-.*$/`), which makes them regular expressions.
+pub fn get_exclude_patterns(patterns: &[String], exclude_bots: bool) -> Result<Vec<Regex>> {
+    let bot_patterns = BOT_PATTERNS
+        .iter()
+        .filter(|_| exclude_bots)
+        .map(|p| p.to_string());
+    let patterns = patterns.iter().map(|p| {
+        match p.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => regex.to_string(),
+            // Names like 'renovate[bot]' shouldn't be read as regular expressions
+            None => get_word_pattern(p),
+        }
+    });
+
+    patterns
+        .chain(bot_patterns)
+        .map(|p| Regex::new(&p).map_err(|source| Error::InvalidPattern { pattern: p, source }))
+        .collect()
+}
+
+/// Return a case insensitive regular expression matching `text` as it is,
+/// unless it's only part of a longer word, so that e.g. 'ann' doesn't exclude 'Joanna'.
+fn get_word_pattern(text: &str) -> String {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(text.chars().next()) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if is_word(text.chars().last()) {
+        r"\b"
+    } else {
+        ""
+    };
+
+    format!("(?i){start}{}{end}", regex::escape(text))
+}
+
+/// Return true if `patterns` is empty, or if one of them is contained in
+/// the `Name <email>` identity of the author, ignoring case.
+pub fn is_author_requested(author_name: &str, author_email: &str, patterns: &[String]) -> bool {
+    if patterns.is_empty() {
+        return true;
+    }
+
+    let identity = format!("{author_name} <{author_email}>").to_lowercase();
+    patterns
+        .iter()
+        .any(|p| identity.contains(&p.to_lowercase()))
+}
+
+/// Return true if either the name or the email of the author matches one of `patterns`.
+pub fn is_author_excluded(author_name: &str, author_email: &str, patterns: &[Regex]) -> bool {
+    patterns
+        .iter()
+        .any(|p| p.is_match(author_name) || p.is_match(author_email))
+}
+
+/// Fill the share of commits and additions of each author,
+/// relative to the totals across all of `authors_data`.
+pub fn compute_percentages(authors_data: &mut [AuthorData]) {
+    let total_commits: usize = authors_data.iter().map(|a| a.num_commits).sum();
+    let total_additions: usize = authors_data.iter().map(|a| a.code_edits.additions).sum();
+
+    for author_data in authors_data {
+        author_data.percent_commits = percentage(author_data.num_commits, total_commits);
+        author_data.percent_additions =
+            percentage(author_data.code_edits.additions, total_additions);
+    }
+}
+
+/// Return `value` as a percentage of `total`, rounded to one decimal place.
+/// An empty `total` returns 0, instead of dividing by zero.
+pub fn percentage(value: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    let percent = value as f64 * 100.0 / total as f64;
+    (percent * 10.0).round() / 10.0
+}
+
+/// Return the number of days from `first` to `last` (both as YYYY-MM-DD, optionally followed
+/// by a time as in RFC 3339), or 0 if either of them can't be parsed or `last` comes first.
+pub fn get_days_between(first: &str, last: &str) -> usize {
+    match (get_days_since_epoch(first), get_days_since_epoch(last)) {
+        (Some(first), Some(last)) => usize::try_from(last - first).unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Return the number of days from 1970-01-01 to `date` (as YYYY-MM-DD),
+/// following http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn get_days_since_epoch(date: &str) -> Option<i64> {
+    let mut parts = date
+        .get(..10)?
+        .splitn(3, '-')
+        .map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// Return an error if `target_dir` isn't inside the work tree of a git repository.
+pub fn ensure_is_git_repo(target_dir: impl AsRef<Path>) -> Result<()> {
+    match get_stdout_from_git_or_fail(&target_dir, ["rev-parse", "--is-inside-work-tree"]) {
+        Ok(stdout) if stdout.trim() == "true" => Ok(()),
+        Err(error @ Error::GitNotFound(_)) => Err(error),
+        _ => Err(Error::NotAGitRepo(target_dir.as_ref().to_path_buf())),
+    }
+}
+
+/// Return an error if a revision of `options` (e.g. the branch) doesn't exist.
+fn ensure_revisions_exist(target_dir: impl AsRef<Path>, options: &QueryOptions) -> Result<()> {
+    if options.all_branches {
+        return Ok(());
+    }
+
+    // The trailing '--' makes git treat the revision as such, rather than as a path.
+    // Ranges like 'v1.0..HEAD' are accepted too
+    let revisions = options.revisions();
+    match get_stdout_from_git_or_fail(&target_dir, ["rev-parse", revisions, "--"]) {
+        Err(Error::GitFailed { .. }) => Err(Error::UnknownRevision(revisions.to_string())),
+        result => result.map(|_| ()),
+    }
+}
+
+/// Return the name of the default branch of the git repository living at `target_dir`.
+/// This is the branch `origin/HEAD` points to if there's a remote, otherwise
+/// the branch that is currently checked out. If neither can be detected
+/// (e.g. in a detached HEAD state), `HEAD` itself is returned.
+pub fn detect_default_branch(target_dir: impl AsRef<Path>) -> String {
+    let remote_args = [
+        "symbolic-ref",
+        "--quiet",
+        "--short",
+        "refs/remotes/origin/HEAD",
+    ];
+    if let Ok(remote_branch) = get_stdout_from_git_or_fail(&target_dir, remote_args) {
+        // Prefer the local branch when there is one, since it's what people usually mean
+        let local_branch = remote_branch
+            .strip_prefix("origin/")
+            .unwrap_or(&remote_branch);
+        let local_ref = format!("refs/heads/{local_branch}");
+        let local_branch_exists = get_stdout_from_git_or_fail(
+            &target_dir,
+            ["rev-parse", "--verify", "--quiet", local_ref.as_str()],
+        )
+        .is_ok();
+
+        let branch = if local_branch_exists {
+            local_branch.to_string()
+        } else {
+            remote_branch.clone()
+        };
+        tracing::info!("Using the default branch '{branch}'");
+        return branch;
+    }
+
+    let head_args = ["symbolic-ref", "--quiet", "--short", "HEAD"];
+    if let Ok(current_branch) = get_stdout_from_git_or_fail(&target_dir, head_args) {
+        tracing::info!("Using the current branch '{current_branch}'");
+        return current_branch;
+    }
+
+    tracing::info!("Failed to detect the default branch, using HEAD");
+    "HEAD".to_string()
+}
+
+/// Return the `git log` flags that restrict the analyzed dates, if any were requested.
+fn get_date_filters(options: &QueryOptions) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    if let Some(since) = &options.since {
+        filters.push(format!("--since={since}"));
+    }
+    if let Some(until) = &options.until {
+        filters.push(format!("--until={until}"));
+    }
+
+    filters
+}
+
+/// Return the `git log` flag that enables or disables `.mailmap` resolution.
+fn get_mailmap_flag(options: &QueryOptions) -> &'static str {
+    if options.use_mailmap {
+        "--use-mailmap"
+    } else {
+        "--no-use-mailmap"
+    }
+}
+
+/// Return all of the commits of the git repository living at `target_dir`
+/// that match `options`, from the most recent to the oldest.
+/// Unless disabled via `options`, the identities of the authors are
+/// the canonical ones from `.mailmap`.
+/// Unless disabled via `options`, the changes to vendored or generated files are skipped,
+/// along with the commits that only changed such files.
+/// `on_commit` is called on each commit as soon as it's parsed.
+pub fn get_commits(
+    target_dir: impl AsRef<Path>,
+    options: &QueryOptions,
+    on_commit: impl FnMut(&Commit),
+) -> Result<Vec<Commit>> {
+    ensure_revisions_exist(&target_dir, options)?;
+
+    // The history of big repos can take a while to go through,
+    // so it's parsed while git is still producing it
+    let mut command = get_git_command(&target_dir, get_log_args(options));
+    let start = Instant::now();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| get_spawn_error(e, &target_dir))?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(Error::InvalidGitOutput {
+            command: describe_command(&command),
+        });
+    };
+    // git would block if it filled the pipe of stderr while stdout is being read
+    let stderr_reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        stderr.read_to_end(&mut output).map(|_| output)
+    });
+
+    let mut read_error = None;
+    let lines = BufReader::new(stdout)
+        .split(b'\n')
+        .map_while(|line| match line {
+            Ok(line) => Some(String::from_utf8_lossy(&line).into_owned()),
+            Err(error) => {
+                read_error = Some(error);
+                None
+            }
+        });
+    let mut commits = parse_log(lines, on_commit);
+    if let Some(error) = read_error {
+        // A partial history would silently give wrong numbers
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(error.into());
+    }
+
+    let status = child.wait()?;
+    let stderr = stderr_reader.join().unwrap_or_else(|_| Ok(Vec::new()))?;
+    let subprocess_result = Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    };
+    ensure_git_succeeded(&command, &subprocess_result, start)?;
+
+    if options.respect_gitattributes {
+        let paths: HashSet<&str> = commits
+            .iter()
+            .flat_map(|c| c.file_changes.iter().map(|f| f.path.as_str()))
+            .collect();
+        let vendored_paths = get_vendored_paths(&target_dir, paths)?;
+        if !vendored_paths.is_empty() {
+            tracing::info!(
+                "Skipping {} vendored or generated files, see .gitattributes",
+                vendored_paths.len()
+            );
+            // Commits that only touched vendored files (e.g. running the codegen) don't count
+            commits.retain_mut(|commit| {
+                let had_changes = !commit.file_changes.is_empty();
+                commit
+                    .file_changes
+                    .retain(|f| !vendored_paths.contains(&f.path));
+                !had_changes || !commit.file_changes.is_empty()
+            });
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Return the `git log` command that `get_commits` runs inside `target_dir`,
+/// quoted so that it can be pasted in a shell, e.g. to debug it.
+pub fn describe_log_command(target_dir: impl AsRef<Path>, options: &QueryOptions) -> String {
+    let target_dir = target_dir.as_ref().to_string_lossy();
+    let mut description = format!("git -C {}", quote_for_shell(&target_dir));
+    for arg in get_log_args(options) {
+        description.push(' ');
+        description.push_str(&quote_for_shell(&arg));
+    }
+
+    description
+}
+
+/// Return `arg` wrapped in single quotes, unless it's only made of characters
+/// that a POSIX shell doesn't interpret.
+fn quote_for_shell(arg: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_=./:,@+".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return Cow::Borrowed(arg);
+    }
+
+    Cow::Owned(format!("'{}'", arg.replace('\'', "'\\''")))
+}
+
+/// Return the `git log` arguments that list the commits matching `options`,
+/// in the format expected by `parse_log`.
+fn get_log_args(options: &QueryOptions) -> Vec<String> {
+    // '%aN' and '%aE' honor .mailmap, while '%an' and '%ae' are the identity
+    // as it was recorded in the commit
+    let identity = if options.use_mailmap {
+        "%aN%x00%aE"
+    } else {
+        "%an%x00%ae"
+    };
+
+    // A single pass over the history is way faster than querying git once per author.
+    // Each commit starts with a NUL, followed by the identity, the author date
+    // (as a timestamp, as YYYY-MM-DD and as RFC 3339), the co-authors and the numstat lines.
+    let mut args = vec!["log".to_string(), options.revisions().to_string()];
+    args.extend(get_date_filters(options));
+    // Without '-m' or '--cc', git doesn't print any numstat for merges,
+    // so their lines can't be credited twice
+    if !options.include_merges {
+        args.push("--no-merges".to_string());
+    }
+    args.push(get_mailmap_flag(options).to_string());
+    args.push("--numstat".to_string());
+    // Renamed files are credited to their authors rather than to whoever moved them,
+    // even if renames were disabled via 'diff.renames' in the git config
+    args.push(if options.follow_renames {
+        "--find-renames".to_string()
+    } else {
+        "--no-renames".to_string()
+    });
+    if options.ignore_whitespace {
+        args.push("--ignore-all-space".to_string());
+    }
+    // %u is the day of the week, from 1 (Monday) to 7 (Sunday), and %H the hour
+    args.push("--date=format:%Y-%m-%d %u %H".to_string());
+    args.push(format!(
+        "--pretty=tformat:%x00{identity}%x00%at%x00%ad%x00%aI%x00%(trailers:key=Co-authored-by,valueonly,separator=%x1f)"
+    ));
+    args.push("--".to_string());
+    args.extend(options.paths.iter().cloned());
+
+    args
+}
+
+/// The attributes used by GitHub Linguist to mark the code that wasn't written by hand.
+const LINGUIST_ATTRIBUTES: [&str; 2] = ["linguist-vendored", "linguist-generated"];
+
+/// Return the `paths` that the `.gitattributes` files of the repo living at `target_dir`
+/// mark as vendored or generated. The paths are relative to the root of the repo.
+pub fn get_vendored_paths<'a>(
+    target_dir: impl AsRef<Path>,
+    paths: impl IntoIterator<Item = &'a str>,
+) -> Result<HashSet<String>> {
+    // Asking git directly means that the patterns follow the same rules as in git,
+    // e.g. 'third_party/** linguist-vendored'
+    let root = get_stdout_from_git_or_fail(&target_dir, ["rev-parse", "--show-toplevel"])?;
+    let mut args = vec!["check-attr", "-z", "--stdin"];
+    args.extend(LINGUIST_ATTRIBUTES);
+    let mut command = get_git_command(root.trim(), args);
+    let start = Instant::now();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| get_spawn_error(e, &target_dir))?;
+    let mut stdin = child.stdin.take().ok_or_else(|| Error::InvalidGitOutput {
+        command: describe_command(&command),
+    })?;
+
+    let input: Vec<u8> = paths
+        .into_iter()
+        .flat_map(|path| path.bytes().chain([b'\0']))
+        .collect();
+    // git starts answering before the input is over, so it must be written while reading
+    let subprocess_result = std::thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(&input));
+        child.wait_with_output()
+    })?;
+    ensure_git_succeeded(&command, &subprocess_result, start)?;
+
+    // Each answer is made of '<path> NUL <attribute> NUL <value> NUL'
+    let stdout = String::from_utf8_lossy(&subprocess_result.stdout);
+    let fields: Vec<&str> = stdout.split('\0').collect();
+    // The output ends with a NUL, hence the extra empty field
+    if fields.len() % 3 != 1 {
+        return Err(Error::InvalidGitOutput {
+            command: describe_command(&command),
+        });
+    }
+    let vendored_paths = fields
+        .chunks_exact(3)
+        .filter(|answer| matches!(answer[2], "set" | "true"))
+        .map(|answer| answer[0].to_string())
+        .collect();
+
+    Ok(vendored_paths)
+}
+
+/// Return the paths of the text files of the analyzed branch, restricted to `options.paths`.
+/// Binary files are skipped, since blaming them doesn't make sense,
+/// and so are the vendored or generated files unless disabled via `options`.
+pub fn get_text_files(target_dir: impl AsRef<Path>, options: &QueryOptions) -> Result<Vec<String>> {
+    // Diffing against the empty tree reports every file, with '-' lines for binary ones.
+    // Hashing an empty stdin works everywhere, unlike /dev/null which doesn't exist on Windows
+    let empty_tree =
+        get_stdout_from_git_or_fail(&target_dir, ["hash-object", "-t", "tree", "--stdin"])?;
+    let mut args = vec![
+        "diff".to_string(),
+        "--numstat".to_string(),
+        "-z".to_string(),
+        empty_tree.trim().to_string(),
+        get_blamed_revision(options).to_string(),
+        "--".to_string(),
+    ];
+    args.extend(options.paths.iter().cloned());
+    let stdout = get_stdout_from_git_or_fail(&target_dir, &args)?;
+
+    let mut files: Vec<String> = stdout
+        .split('\0')
+        .filter_map(|record| {
+            let mut tokens = record.splitn(3, '\t');
+            let additions = tokens.next()?;
+            let _removals = tokens.next()?;
+            let path = tokens.next()?;
+            (additions != "-").then(|| path.to_string())
+        })
+        .collect();
+
+    if options.respect_gitattributes {
+        let vendored_paths = get_vendored_paths(&target_dir, files.iter().map(String::as_str))?;
+        files.retain(|f| !vendored_paths.contains(f));
+    }
+
+    Ok(files)
+}
+
+/// Blame `files` (e.g. as returned by [`get_text_files`]) in parallel, and return the number
+/// of lines that each author (by email) touched last, in no particular order.
+/// The authors always honor `.mailmap`, since that's what `git blame` does.
+/// Files that can't be blamed are skipped with a warning.
+/// At most `options.jobs` files are blamed at the same time.
+/// `on_file` is called on each file once it's blamed, from any of the threads.
+pub fn get_blame_data(
+    target_dir: impl AsRef<Path>,
+    options: &QueryOptions,
+    files: &[String],
+    on_file: impl Fn(&str) + Sync,
+) -> Vec<BlameData> {
+    let target_dir = target_dir.as_ref();
+    let next_file = AtomicUsize::new(0);
+    let num_workers = match options.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    let lines_by_identity: Vec<HashMap<(String, String), usize>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut lines_by_identity: HashMap<(String, String), usize> = HashMap::new();
+                    loop {
+                        let i = next_file.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(i) else {
+                            break;
+                        };
+
+                        let mut args = vec!["blame", "--line-porcelain"];
+                        // The lines that were only reindented keep their original author
+                        if options.ignore_whitespace {
+                            args.push("-w");
+                        }
+                        args.extend([get_blamed_revision(options), "--", file.as_str()]);
+                        match get_stdout_from_git_or_fail(target_dir, args) {
+                            Ok(stdout) => {
+                                for identity in parse_blame(&stdout) {
+                                    *lines_by_identity.entry(identity).or_default() += 1;
+                                }
+                            }
+                            Err(err) => tracing::warn!("Failed to blame '{file}': {err}"),
+                        }
+                        on_file(file);
+                    }
+                    lines_by_identity
+                })
+            })
+            .collect();
+
+        workers.into_iter().filter_map(|w| w.join().ok()).collect()
+    });
+
+    // If the same email was used with different names, the one owning the most lines wins
+    let mut blame_data: HashMap<String, (BlameData, usize)> = HashMap::new();
+    let mut merged: HashMap<(String, String), usize> = HashMap::new();
+    for partial in lines_by_identity {
+        for (identity, lines) in partial {
+            *merged.entry(identity).or_default() += lines;
+        }
+    }
+    let mut merged: Vec<((String, String), usize)> = merged.into_iter().collect();
+    merged.sort();
+    for ((name, email), lines) in merged {
+        let (data, best_name_lines) = blame_data.entry(email.clone()).or_insert_with(|| {
+            let data = BlameData {
+                author_name: name.clone(),
+                author_email: Some(email),
+                lines_owned: 0,
+                percent_lines: 0.0,
+            };
+            (data, 0)
+        });
+        data.lines_owned += lines;
+        if lines > *best_name_lines {
+            data.author_name = name;
+            *best_name_lines = lines;
+        }
+    }
+
+    blame_data.into_values().map(|(data, _)| data).collect()
+}
+
+/// The revision whose files are blamed, since blaming several branches at once doesn't make sense.
+fn get_blamed_revision(options: &QueryOptions) -> &str {
+    if options.branch_name.is_empty() {
+        "HEAD"
+    } else {
+        &options.branch_name
+    }
+}
+
+/// Parse the output of `git blame --line-porcelain`, returning the
+/// name and email of the author of each line.
+fn parse_blame(blame: &str) -> Vec<(String, String)> {
+    let mut identities = Vec::new();
+    let mut name = "";
+
+    for line in blame.lines() {
+        // The content of each line starts with a tab, so it can't be mistaken for a header
+        if let Some(author) = line.strip_prefix("author ") {
+            name = author;
+        } else if let Some(mail) = line.strip_prefix("author-mail ") {
+            let email = mail.trim_start_matches('<').trim_end_matches('>');
+            identities.push((name.to_string(), email.to_string()));
+        }
+    }
+
+    identities
+}
+
+/// Parse the lines of `git log --numstat --pretty=tformat:%x00<name>%x00<email>%x00<timestamp>%x00<date>%x00<datetime>%x00<co-authors>`,
+/// where the co-authors are `Name <email>` values separated by `0x1f`.
+/// Commits without an author name are skipped, while `on_commit` is called
+/// on every other commit as soon as its header is parsed.
+fn parse_log(
+    lines: impl IntoIterator<Item = impl AsRef<str>>,
+    mut on_commit: impl FnMut(&Commit),
+) -> Vec<Commit> {
+    let mut commits: Vec<Commit> = Vec::new();
+    let mut is_valid_commit = false;
+
+    for line in lines {
+        // git for Windows can be configured to end its lines with CRLF
+        let line = line.as_ref();
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(identity) = line.strip_prefix('\0') {
+            is_valid_commit = false;
+
+            let mut fields = identity.split('\0');
+            let (Some(name), Some(email)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            if name.trim().is_empty() {
+                continue;
+            }
+
+            let timestamp = fields.next().and_then(|t| t.parse().ok()).unwrap_or(0);
+            let mut date_fields = fields.next().unwrap_or_default().split(' ');
+            let date = date_fields.next().unwrap_or_default();
+            let weekday = date_fields.next().unwrap_or_default();
+            let hour = date_fields.next().unwrap_or_default();
+            let datetime = fields.next().unwrap_or_default();
+            commits.push(Commit {
+                author_name: name.trim().to_string(),
+                author_email: email.to_string(),
+                timestamp,
+                date: date.to_string(),
+                datetime: datetime.to_string(),
+                weekday: weekday
+                    .parse::<usize>()
+                    .map_or(0, |d| d.saturating_sub(1) % 7),
+                hour: hour.parse::<usize>().map_or(0, |h| h % 24),
+                co_authors: fields
+                    .next()
+                    .unwrap_or_default()
+                    .split('\x1f')
+                    .filter_map(parse_identity)
+                    .collect(),
+                is_coauthored: false,
+                file_changes: Vec::new(),
+            });
+            if let Some(commit) = commits.last() {
+                on_commit(commit);
+            }
+            is_valid_commit = true;
+            continue;
+        }
+
+        if !is_valid_commit {
+            continue;
+        }
+        if let (Some(commit), Some(file_change)) = (commits.last_mut(), parse_numstat_line(line)) {
+            commit.file_changes.push(file_change);
+        }
+    }
+
+    commits
+}
+
+/// Parse a `Name <email>` identity, e.g. from a `Co-authored-by` trailer.
+fn parse_identity(identity: &str) -> Option<(String, String)> {
+    let (name, email) = identity.trim().strip_suffix('>')?.rsplit_once('<')?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| (name.to_string(), email.trim().to_string()))
+}
+
+/// Give each co-author of `commits` a copy of the commit, credited to them.
+/// Co-authors listed more than once, or as the author themselves, are only credited once.
+/// If `split_lines` is set, the lines of the commit are split evenly between the author
+/// and the co-authors (the author gets the remainder), otherwise each of them gets all of them.
+pub fn credit_coauthors(commits: Vec<Commit>, split_lines: bool) -> Vec<Commit> {
+    let mut credited_commits = Vec::with_capacity(commits.len());
+
+    for mut commit in commits {
+        let mut emails = vec![commit.author_email.to_lowercase()];
+        let mut co_authors = Vec::new();
+        for (name, email) in &commit.co_authors {
+            if emails.contains(&email.to_lowercase()) {
+                continue;
+            }
+            emails.push(email.to_lowercase());
+            co_authors.push((name.clone(), email.clone()));
+        }
+
+        let num_people = if split_lines { emails.len() } else { 1 };
+        let mut shared_changes = commit.file_changes.clone();
+        for (shared_change, file_change) in shared_changes.iter_mut().zip(&mut commit.file_changes)
+        {
+            shared_change.additions = file_change.additions / num_people;
+            shared_change.removals = file_change.removals / num_people;
+            file_change.additions -= shared_change.additions * (num_people - 1);
+            file_change.removals -= shared_change.removals * (num_people - 1);
+        }
+
+        for (name, email) in co_authors {
+            credited_commits.push(Commit {
+                author_name: name,
+                author_email: email,
+                co_authors: Vec::new(),
+                is_coauthored: true,
+                file_changes: shared_changes.clone(),
+                ..commit.clone()
+            });
+        }
+        credited_commits.push(commit);
+    }
+
+    credited_commits
+}
+
+/// Parse a single `<additions>\t<removals>\t<path>` line of `git log --numstat`.
+/// Binary files are reported by git as `-\t-\t<path>`.
+fn parse_numstat_line(line: &str) -> Option<FileChange> {
+    let mut tokens = line.splitn(3, '\t');
+    let additions = tokens.next()?;
+    let removals = tokens.next()?;
+    let (path, renamed_from) = parse_numstat_path(tokens.next()?);
+
+    Some(FileChange {
+        path,
+        renamed_from,
+        additions: additions.trim().parse().unwrap_or(0),
+        removals: removals.trim().parse().unwrap_or(0),
+        is_binary: additions == "-" && removals == "-",
+    })
+}
+
+/// Parse the path of a numstat line, where renames look like `old => new`
+/// or `common/{old => new}/path`. Return the new path and the old one, if any.
+fn parse_numstat_path(path: &str) -> (String, Option<String>) {
+    let Some((before, after)) = path.split_once(" => ") else {
+        return (path.to_string(), None);
+    };
+
+    match (before.rfind('{'), after.find('}')) {
+        (Some(open), Some(close)) => {
+            let prefix = &before[..open];
+            let old = &before[open + 1..];
+            let new = &after[..close];
+            let suffix = &after[close + 1..];
+
+            // An empty side means that a directory level was added or removed,
+            // e.g. 'src/{ => lib}/main.rs', so don't leave a double slash behind
+            let join = |middle: &str| {
+                let joined = format!("{prefix}{middle}{suffix}");
+                joined.replace("//", "/")
+            };
+            (join(new), Some(join(old)))
+        }
+        _ => (after.to_string(), Some(before.to_string())),
+    }
+}
+
+/// Return the name to use for each email: the one used the most in `commits`,
+/// or the first alphabetically in case of ties.
+pub fn get_names_by_email(commits: &[Commit]) -> HashMap<&str, &str> {
+    let mut name_counts: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+    for commit in commits {
+        *name_counts
+            .entry(&commit.author_email)
+            .or_default()
+            .entry(&commit.author_name)
+            .or_default() += 1;
+    }
+
+    name_counts
+        .into_iter()
+        .filter_map(|(email, counts)| {
+            let (name, _) = counts
+                .into_iter()
+                .max_by(|(name_a, count_a), (name_b, count_b)| {
+                    count_a.cmp(count_b).then_with(|| name_b.cmp(name_a))
+                })?;
+            Some((email, name))
+        })
+        .collect()
+}
+
+/// Aggregate the number of commits and edits of each author (by email),
+/// sorted lexicographically by name and email (by byte values).
+/// If the same email was used with different names, the most used name is kept.
+/// For more notes on the sorting, see:
+/// <https://doc.rust-lang.org/std/primitive.str.html#impl-Ord>
+pub fn aggregate_by_author(commits: &[Commit]) -> Vec<AuthorData> {
+    let names_by_email = get_names_by_email(commits);
+    let mut authors_data: HashMap<&str, AuthorData> = HashMap::new();
+    let mut active_days: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut first_timestamps: HashMap<&str, i64> = HashMap::new();
+
+    for commit in commits {
+        let email = commit.author_email.as_str();
+        let author_data = authors_data
+            .entry(email)
+            .or_insert_with(|| AuthorData::new(names_by_email[email], email));
+
+        author_data.num_commits += 1;
+        author_data.commits_per_weekday[commit.weekday] += 1;
+        author_data.commits_per_hour[commit.hour] += 1;
+        let mut commit_edits = CodeEdits::default();
+        for file_change in &commit.file_changes {
+            commit_edits.add(file_change);
+        }
+        author_data.code_edits.additions += commit_edits.additions;
+        author_data.code_edits.removals += commit_edits.removals;
+        author_data.code_edits.binary_changes += commit_edits.binary_changes;
+        author_data.largest_commit_lines = author_data
+            .largest_commit_lines
+            .max(commit_edits.changed_lines());
+
+        // The history isn't sorted by author date, e.g. because of rebases.
+        // The timestamps are compared, since the dates may be in different timezones
+        if !commit.datetime.is_empty() {
+            let first_timestamp = first_timestamps.entry(email).or_insert(i64::MAX);
+            if commit.timestamp < *first_timestamp {
+                *first_timestamp = commit.timestamp;
+                author_data.first_commit = Some(commit.datetime.clone());
+            }
+            if author_data.last_commit.is_none()
+                || commit.timestamp > author_data.last_commit_timestamp
+            {
+                author_data.last_commit_timestamp = commit.timestamp;
+                author_data.last_commit = Some(commit.datetime.clone());
+            }
+        }
+        if !commit.date.is_empty() {
+            active_days.entry(email).or_default().insert(&commit.date);
+        }
+    }
+
+    for (email, author_data) in authors_data.iter_mut() {
+        author_data.active_days = active_days.get(email).map_or(0, HashSet::len);
+        if let (Some(first), Some(last)) = (&author_data.first_commit, &author_data.last_commit) {
+            author_data.active_span_days = get_days_between(first, last);
+        }
+        if author_data.num_commits > 0 {
+            let avg =
+                author_data.code_edits.changed_lines() as f64 / author_data.num_commits as f64;
+            author_data.avg_lines_per_commit = (avg * 10.0).round() / 10.0;
+        }
+    }
+
+    let mut authors_data: Vec<AuthorData> = authors_data.into_values().collect();
+    authors_data
+        .sort_by(|a, b| (&a.author_name, &a.author_email).cmp(&(&b.author_name, &b.author_email)));
+
+    authors_data
+}
+
+/// Aggregate the edits made to each file, grouped under the path returned by `group_path`,
+/// keeping the `authors_per_file` authors who changed the most lines.
+/// The results are sorted by the number of changed lines.
+/// Renamed files are tracked under their most recent path, so that
+/// their history isn't split, assuming `commits` go from the newest to the oldest.
+pub fn aggregate_by_path(
+    commits: &[Commit],
+    authors_per_file: usize,
+    group_path: impl Fn(&str) -> String,
+) -> Vec<FileData> {
+    let names_by_email = get_names_by_email(commits);
+    let mut renames: HashMap<&str, String> = HashMap::new();
+    let mut edits: HashMap<String, HashMap<&str, CodeEdits>> = HashMap::new();
+
+    for commit in commits {
+        for file_change in &commit.file_changes {
+            let path = renames
+                .get(file_change.path.as_str())
+                .cloned()
+                .unwrap_or_else(|| file_change.path.clone());
+            if let Some(old_path) = &file_change.renamed_from {
+                renames.insert(old_path, path.clone());
+            }
+
+            let code_edits = edits
+                .entry(group_path(&path))
+                .or_default()
+                .entry(&commit.author_email)
+                .or_default();
+            code_edits.add(file_change);
+        }
+    }
+
+    let mut files_data: Vec<FileData> = edits
+        .into_iter()
+        .map(|(path, edits_by_email)| {
+            let mut authors: Vec<FileAuthorData> = edits_by_email
+                .into_iter()
+                .map(|(email, code_edits)| FileAuthorData {
+                    author_name: names_by_email[email].to_string(),
+                    author_email: Some(email.to_string()),
+                    code_edits,
+                    percent_changed_lines: 0.0,
+                })
+                .collect();
+            authors.sort_by(|a, b| {
+                b.code_edits
+                    .changed_lines()
+                    .cmp(&a.code_edits.changed_lines())
+                    .then_with(|| a.author_name.cmp(&b.author_name))
+                    .then_with(|| a.author_email.cmp(&b.author_email))
+            });
+
+            let code_edits = CodeEdits {
+                additions: authors.iter().map(|a| a.code_edits.additions).sum(),
+                removals: authors.iter().map(|a| a.code_edits.removals).sum(),
+                binary_changes: authors.iter().map(|a| a.code_edits.binary_changes).sum(),
+            };
+            for author in &mut authors {
+                author.percent_changed_lines = percentage(
+                    author.code_edits.changed_lines(),
+                    code_edits.changed_lines(),
+                );
+            }
+            authors.truncate(authors_per_file);
+
+            FileData {
+                path,
+                code_edits,
+                authors,
+            }
+        })
+        .collect();
+
+    files_data.sort_by(|a, b| {
+        b.code_edits
+            .changed_lines()
+            .cmp(&a.code_edits.changed_lines())
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    files_data
+}
+
+/// Return the command that runs `git` with the given `args` inside `target_dir`.
+fn get_git_command<I, S>(target_dir: impl AsRef<Path>, args: I) -> Command
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    // No shell is involved, so the arguments never need to be quoted
+    let mut command = Command::new("git");
+    command.current_dir(target_dir.as_ref()).args(args);
+    tracing::debug!("Running {command:?}");
+
+    command
+}
+
+/// Return `command` as it would be typed in a shell, e.g. `git log HEAD`.
+fn describe_command(command: &Command) -> String {
+    let mut description = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        description.push(' ');
+        description.push_str(&arg.to_string_lossy());
+    }
+
+    description
+}
+
+/// Return the error of a git command that couldn't be started inside `target_dir`.
+/// A missing directory is reported the same way as a missing git executable,
+/// so the latter is only assumed when the directory exists.
+fn get_spawn_error(error: std::io::Error, target_dir: impl AsRef<Path>) -> Error {
+    if error.kind() == std::io::ErrorKind::NotFound && target_dir.as_ref().is_dir() {
+        Error::GitNotFound(error)
+    } else {
+        Error::Io(error)
+    }
+}
+
+/// Log how long `command` took since `start` and return an error if it didn't exit with 0.
+/// Some failures are expected (e.g. looking for a remote), so `stderr` is only a debug message.
+fn ensure_git_succeeded(command: &Command, result: &Output, start: Instant) -> Result<()> {
+    let subcommand = command
+        .get_args()
+        .next()
+        .unwrap_or_default()
+        .to_string_lossy();
+    tracing::debug!("'git {subcommand}' took {:.2?}", start.elapsed());
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        if !stderr.trim().is_empty() {
+            tracing::debug!("stderr from subprocess: {}", stderr.trim_end());
+        }
+        return Err(Error::GitFailed {
+            command: describe_command(command),
+            stderr: stderr.trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Run `git` with the given `args` inside `target_dir` and return back `stdout` if
+/// it exited with 0. If git exited with non 0 this will return an error
+/// with `stderr`.
+pub fn get_stdout_from_git_or_fail<I, S>(target_dir: impl AsRef<Path>, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command = get_git_command(&target_dir, args);
+    let start = Instant::now();
+    let subprocess_result = command
+        .output()
+        .map_err(|e| get_spawn_error(e, &target_dir))?;
+    ensure_git_succeeded(&command, &subprocess_result, start)?;
+
+    Ok(decode_stdout(&subprocess_result.stdout))
+}
+
+/// Return the `stdout` of git as a string with `\n` line endings, without the trailing ones.
+fn decode_stdout(stdout: &[u8]) -> String {
+    // A single author name in Latin-1 (e.g. in a blame) shouldn't discard the whole output
+    let stdout = String::from_utf8_lossy(stdout);
+    // Some configs (e.g. on Windows) end the lines with CRLF
+    let stdout = if stdout.contains('\r') {
+        stdout.replace("\r\n", "\n")
+    } else {
+        stdout.into_owned()
+    };
+
+    stdout.trim_end_matches(['\r', '\n']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a commit of `name <email>` changing `path` by `additions` and `removals` lines.
+    fn make_commit(
+        name: &str,
+        email: &str,
+        path: &str,
+        additions: usize,
+        removals: usize,
+    ) -> Commit {
+        Commit {
+            author_name: name.to_string(),
+            author_email: email.to_string(),
+            timestamp: 0,
+            date: "2024-01-01".to_string(),
+            datetime: "2024-01-01T00:00:00+00:00".to_string(),
+            weekday: 0,
+            hour: 0,
+            co_authors: Vec::new(),
+            is_coauthored: false,
+            file_changes: vec![FileChange {
+                path: path.to_string(),
+                renamed_from: None,
+                additions,
+                removals,
+                is_binary: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn parse_log_reads_headers_and_numstat_lines() {
+        let lines = [
+            "\0Jane Doe\0jane@example.com\x001704103200\x002024-01-01 1 10\x002024-01-01T10:00:00+00:00\0Ann <ann@example.com>",
+            "",
+            "3\t1\tsrc/main.rs",
+            "-\t-\tlogo.png",
+            "\0John\0john@example.com\x001704276000\x002024-01-03 3 10\x002024-01-03T10:00:00+00:00\0",
+            "",
+            "1\t0\tREADME.md",
+        ];
+        let mut num_parsed = 0;
+        let commits = parse_log(lines, |_| num_parsed += 1);
+
+        assert_eq!(num_parsed, 2);
+        assert_eq!(commits.len(), 2);
+
+        assert_eq!(commits[0].author_name, "Jane Doe");
+        assert_eq!(commits[0].author_email, "jane@example.com");
+        assert_eq!(commits[0].timestamp, 1704103200);
+        assert_eq!(commits[0].date, "2024-01-01");
+        assert_eq!(commits[0].datetime, "2024-01-01T10:00:00+00:00");
+        assert_eq!(commits[0].weekday, 0);
+        assert_eq!(commits[0].hour, 10);
+        assert_eq!(
+            commits[0].co_authors,
+            vec![("Ann".to_string(), "ann@example.com".to_string())]
+        );
+        assert_eq!(commits[0].file_changes.len(), 2);
+        assert!(commits[0].file_changes[1].is_binary);
+
+        assert_eq!(commits[1].date, "2024-01-03");
+        assert_eq!(commits[1].weekday, 2);
+        assert!(commits[1].co_authors.is_empty());
+        assert_eq!(commits[1].file_changes[0].path, "README.md");
+    }
+
+    #[test]
+    fn parse_log_skips_commits_without_an_author_name() {
+        let lines = [
+            "\0 \0nobody@example.com\x000\x002024-01-01 1 00\x002024-01-01T00:00:00+00:00\0",
+            "5\t5\tignored.rs",
+            "\0Jane\0jane@example.com\x000\x002024-01-01 1 00\x002024-01-01T00:00:00+00:00\0",
+            "1\t2\tkept.rs",
+        ];
+        let commits = parse_log(lines, |_| {});
+
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].author_name, "Jane");
+        assert_eq!(commits[0].file_changes.len(), 1);
+        assert_eq!(commits[0].file_changes[0].path, "kept.rs");
+    }
+
+    #[test]
+    fn parse_log_ignores_blank_and_malformed_lines() {
+        let lines = [
+            "",
+            "\0Jane Doe\0jane@example.com\0a1",
+            "",
+            "   ",
+            "1\t2\tsrc/main.rs",
+            "not a numstat line",
+            "\0\0nobody@example.com\0a2",
+            "",
+        ];
+
+        let commits = parse_log(lines, |_| {});
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].author_name, "Jane Doe");
+        assert_eq!(commits[0].file_changes.len(), 1);
+        assert_eq!(commits[0].file_changes[0].additions, 1);
+        assert_eq!(commits[0].file_changes[0].removals, 2);
+
+        assert!(parse_log(["", "", ""], |_| {}).is_empty());
+    }
+
+    #[test]
+    fn parse_log_handles_crlf_line_endings() {
+        let output = "\0Jane Doe\0jane@example.com\x001704099600\x002024-01-01 1 09\x002024-01-01T09:00:00+00:00\r\n\
+                      \r\n\
+                      3\t1\tsrc/main.rs\r\n\
+                      -\t-\tlogo.png\r\n";
+        let commits = parse_log(output.split('\n'), |_| {});
+
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].author_name, "Jane Doe");
+        assert_eq!(commits[0].author_email, "jane@example.com");
+        assert_eq!(commits[0].timestamp, 1704099600);
+        assert_eq!(commits[0].date, "2024-01-01");
+        let paths: Vec<&str> = commits[0]
+            .file_changes
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        assert_eq!(paths, ["src/main.rs", "logo.png"]);
+    }
+
+    #[test]
+    fn exclude_patterns_match_whole_words_ignoring_case() {
+        let is_excluded = |pattern: &str, name: &str, email: &str| {
+            let patterns = get_exclude_patterns(&[pattern.to_string()], false).unwrap();
+            is_author_excluded(name, email, &patterns)
+        };
+
+        assert!(is_excluded(
+            "renovate[bot]",
+            "renovate[bot]",
+            "bot@renovateapp.com"
+        ));
+        assert!(!is_excluded("renovate[bot]", "renovate", "r@example.com"));
+        assert!(is_excluded("jenkins", "Jenkins CI", "ci@example.com"));
+        assert!(is_excluded(
+            "@CORP.example",
+            "Jane",
+            "jane@corp.example.com"
+        ));
+        assert!(is_excluded("Foo (", "Foo (contractor)", "foo@example.com"));
+        assert!(!is_excluded("a.b", "axb", "axb@example.com"));
+
+        // Short values don't exclude the authors whose names merely contain them
+        assert!(is_excluded("ann", "Ann Lee", "lee@example.com"));
+        assert!(!is_excluded("ann", "Joanna", "joanna@example.com"));
+        assert!(!is_excluded("bot", "Abbot", "abbot@example.com"));
+
+        // Slashes make a regular expression
+        assert!(is_excluded("/^ci-.*$/", "ci-runner", "ci@example.com"));
+        assert!(!is_excluded("/^ci-/", "Jane ci-fan", "jane@example.com"));
+        assert!(get_exclude_patterns(&["/(/".to_string()], false).is_err());
+    }
+
+    #[test]
+    fn exclude_bots_matches_the_common_bots() {
+        let patterns = get_exclude_patterns(&[], true).unwrap();
+        let is_bot = |name: &str, email: &str| is_author_excluded(name, email, &patterns);
+
+        assert!(is_bot(
+            "dependabot[bot]",
+            "49699333+dependabot[bot]@users.noreply.github.com"
+        ));
+        assert!(is_bot("github-actions", "actions@github.com"));
+        assert!(is_bot("Renovate Bot", "bot@renovateapp.com"));
+        assert!(!is_bot("Abbot", "abbot@example.com"));
+        assert!(!is_bot("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn decode_stdout_normalizes_the_line_endings() {
+        assert_eq!(decode_stdout(b"main\n"), "main");
+        assert_eq!(decode_stdout(b"main\r\n"), "main");
+        assert_eq!(decode_stdout(b"Jane\r\nJohn\r\n\r\n"), "Jane\nJohn");
+        assert_eq!(decode_stdout(b"a b\tc\n"), "a b\tc");
+        assert_eq!(decode_stdout(b"a.txt\0b.txt\0"), "a.txt\0b.txt\0");
+        assert_eq!(decode_stdout(b""), "");
+    }
+
+    #[test]
+    fn decode_stdout_keeps_the_rest_of_invalid_utf8() {
+        assert_eq!(
+            decode_stdout(b"Jane\r\nJos\xe9\r\nJohn\r\n"),
+            "Jane\nJos\u{fffd}\nJohn"
+        );
+    }
+
+    #[test]
+    fn parse_numstat_line_reads_text_and_binary_files() {
+        let change = parse_numstat_line("12\t3\tsrc/lib.rs").unwrap();
+        assert_eq!(change.path, "src/lib.rs");
+        assert_eq!((change.additions, change.removals), (12, 3));
+        assert!(!change.is_binary);
+        assert_eq!(change.renamed_from, None);
+
+        let change = parse_numstat_line("-\t-\tassets/logo.png").unwrap();
+        assert_eq!((change.additions, change.removals), (0, 0));
+        assert!(change.is_binary);
+
+        let change = parse_numstat_line("0\t0\tsrc/{old => new}/mod.rs").unwrap();
+        assert_eq!(change.path, "src/new/mod.rs");
+        assert_eq!(change.renamed_from.as_deref(), Some("src/old/mod.rs"));
+
+        assert_eq!(parse_numstat_line(""), None);
+        assert_eq!(parse_numstat_line("not a numstat line"), None);
+    }
+
+    #[test]
+    fn parse_numstat_path_handles_renames() {
+        assert_eq!(
+            parse_numstat_path("src/main.rs"),
+            ("src/main.rs".to_string(), None)
+        );
+        assert_eq!(
+            parse_numstat_path("old.rs => new.rs"),
+            ("new.rs".to_string(), Some("old.rs".to_string()))
+        );
+        assert_eq!(
+            parse_numstat_path("src/{a => b}/lib.rs"),
+            ("src/b/lib.rs".to_string(), Some("src/a/lib.rs".to_string()))
+        );
+        assert_eq!(
+            parse_numstat_path("src/{ => lib}/main.rs"),
+            (
+                "src/lib/main.rs".to_string(),
+                Some("src/main.rs".to_string())
+            )
+        );
+        assert_eq!(
+            parse_numstat_path("{docs => doc}/README.md"),
+            (
+                "doc/README.md".to_string(),
+                Some("docs/README.md".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn parse_identity_needs_a_name_and_an_email() {
+        assert_eq!(
+            parse_identity(" Jane Doe <jane@example.com> "),
+            Some(("Jane Doe".to_string(), "jane@example.com".to_string()))
+        );
+        assert_eq!(parse_identity("<jane@example.com>"), None);
+        assert_eq!(parse_identity("Jane Doe"), None);
+        assert_eq!(parse_identity(""), None);
+    }
+
+    #[test]
+    fn credit_coauthors_gives_a_copy_to_each_coauthor() {
+        let mut commit = make_commit("Jane", "jane@example.com", "src/lib.rs", 10, 4);
+        commit.co_authors = vec![
+            ("Ann".to_string(), "ann@example.com".to_string()),
+            // Listed twice, and once as the commit author
+            ("Ann".to_string(), "ANN@example.com".to_string()),
+            ("Jane".to_string(), "jane@example.com".to_string()),
+        ];
+
+        let commits = credit_coauthors(vec![commit], false);
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].author_name, "Ann");
+        assert!(commits[0].is_coauthored);
+        assert_eq!(commits[0].file_changes[0].additions, 10);
+        assert_eq!(commits[1].author_name, "Jane");
+        assert!(!commits[1].is_coauthored);
+        assert_eq!(commits[1].file_changes[0].additions, 10);
+    }
+
+    #[test]
+    fn credit_coauthors_can_split_the_lines() {
+        let mut commit = make_commit("Jane", "jane@example.com", "src/lib.rs", 10, 4);
+        commit.co_authors = vec![
+            ("Ann".to_string(), "ann@example.com".to_string()),
+            ("Bob".to_string(), "bob@example.com".to_string()),
+        ];
+
+        let commits = credit_coauthors(vec![commit], true);
+
+        let edits: Vec<(usize, usize)> = commits
+            .iter()
+            .map(|c| (c.file_changes[0].additions, c.file_changes[0].removals))
+            .collect();
+        // The author gets the remainder, so that no line is lost
+        assert_eq!(edits, vec![(3, 1), (3, 1), (4, 2)]);
+    }
+
+    #[test]
+    fn aggregate_by_author_sums_the_commits_of_each_email() {
+        let commits = vec![
+            make_commit("Jane", "jane@example.com", "a.rs", 3, 1),
+            make_commit("Jane", "jane@example.com", "b.rs", 2, 0),
+            make_commit("John", "john@example.com", "a.rs", 1, 1),
+        ];
+
+        let authors_data = aggregate_by_author(&commits);
+
+        assert_eq!(authors_data.len(), 2);
+        assert_eq!(authors_data[0].author_name, "Jane");
+        assert_eq!(authors_data[0].num_commits, 2);
+        assert_eq!(authors_data[0].code_edits.additions, 5);
+        assert_eq!(authors_data[0].code_edits.removals, 1);
+        assert_eq!(authors_data[1].author_name, "John");
+        assert_eq!(authors_data[1].num_commits, 1);
+    }
+
+    #[test]
+    fn parse_blame_returns_the_author_of_each_line() {
+        let blame = "\
+0123456789abcdef0123456789abcdef01234567 1 1 2
+author Jane Doe
+author-mail <jane@example.com>
+author-time 1704103200
+summary First commit
+filename src/lib.rs
+\tfn main() {
+0123456789abcdef0123456789abcdef01234567 2 2
+author Jane Doe
+author-mail <jane@example.com>
+author-time 1704103200
+summary First commit
+filename src/lib.rs
+\t}
+fedcba9876543210fedcba9876543210fedcba98 3 3 1
+author John
+author-mail <john@example.com>
+summary Second commit
+filename src/lib.rs
+\tauthor this line isn't a header
+";
+
+        assert_eq!(
+            parse_blame(blame),
+            vec![
+                ("Jane Doe".to_string(), "jane@example.com".to_string()),
+                ("Jane Doe".to_string(), "jane@example.com".to_string()),
+                ("John".to_string(), "john@example.com".to_string()),
+            ]
+        );
+    }
+}
@@ -1,226 +1,1832 @@
+use std::ffi::OsString;
 use std::fmt::Display;
-use std::io::Write;
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
-use clap::Parser;
-use color_eyre::{eyre, Section};
-use shell_quote::Bash;
+use clap::parser::ValueSource;
+use clap::{
+    ArgAction, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use color_eyre::{eyre, eyre::WrapErr, Section};
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
+use serde::Serialize;
 use tracing_subscriber::FmtSubscriber;
 
+mod activity;
+mod authors;
+mod blame;
+mod bus_factor;
+mod cache;
+mod codeowners;
+mod compare;
+mod exclusions;
+mod files;
+mod identities;
+mod output;
+mod tui;
+
+use whos_done_that::{
+    credit_coauthors, describe_log_command, detect_default_branch, ensure_is_git_repo, get_commits,
+    get_exclude_patterns, is_author_excluded, is_author_requested, Commit, QueryOptions,
+};
+
+use crate::activity::{write_timeline_report, write_weekday_report, write_when_report};
+use crate::authors::write_author_report;
+use crate::blame::write_blame_report;
+use crate::bus_factor::write_bus_factor_report;
+use crate::cache::{clear_cache, get_cache_entry, CacheEntry};
+use crate::codeowners::{write_codeowners, write_codeowners_check};
+use crate::compare::{write_compare_report, CompareSides};
+use crate::exclusions::{get_excluded_paths, is_path_excluded, PathPattern};
+use crate::files::{write_directory_report, write_file_report, write_orphan_risk_report};
+use crate::identities::{
+    anonymize_authors, assign_domains, assign_teams, read_domain_map, read_teams,
+};
+use crate::output::{create_output_file, Palette};
+
 #[derive(Parser, Debug)]
 #[command(
     version,
     long_about = "A CLI to help establish ownernship of codebases"
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        help = "The target directory to analyze. It must be a git repo. If not provided, the current directory will be used instead. Can be repeated to analyze multiple repos, each with its own report."
+    )]
+    target_dir: Vec<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Combine the stats of all of the target directories in a single report, where the authors are matched by email."
+    )]
+    aggregate: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Stop at the first target directory that can't be analyzed, instead of skipping it."
+    )]
+    fail_fast: bool,
+
+    #[arg(
+        global = true,
+        long,
+        conflicts_with = "target_dir",
+        help = "Read the target directories from stdin, one per line, e.g. to analyze a list of repos in a single run."
+    )]
+    stdin: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Print the 'git log' command that would be run for each target directory on stderr, then exit without running it nor writing any report. The default branch is still detected, unless '--branch' or a range is passed."
+    )]
+    dry_run: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Analyze every git repo found under the target directories, instead of the target directories themselves. Hidden directories and the insides of the repos that were found are skipped."
+    )]
+    recursive: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        requires = "recursive",
+        help = "How many levels of directories to go through when looking for git repos with '--recursive'. Unlimited by default."
+    )]
+    max_depth: Option<usize>,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        help = "Branch name used to search for commit authors. If not provided, the default branch of the repo is detected automatically."
+    )]
+    branch: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        conflicts_with = "branch",
+        help = "Analyze the commits reachable from any branch, tag or remote, instead of a single branch."
+    )]
+    all_branches: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "REVSPEC",
+        conflicts_with_all = ["branch", "all_branches"],
+        help = "Only analyze the commits of the given range instead of a whole branch, e.g. 'v1.0..v2.0'. Accepts any revision range git understands."
+    )]
+    range: Option<String>,
+
+    #[arg(
+        value_name = "REVSPEC",
+        conflicts_with_all = ["branch", "all_branches", "range"],
+        help = "Same as '--range', e.g. 'whos-done-that v1.0..v2.0'. A single revision (like a tag) analyzes its whole history."
+    )]
+    revspec: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "DATE",
+        help = "Only analyze commits more recent than the given date. Accepts any date format git understands, e.g. '2024-01-01' or '6 months ago'."
+    )]
+    since: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "DATE",
+        help = "Only analyze commits older than the given date. Accepts any date format git understands."
+    )]
+    until: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATHSPEC",
+        help = "Only analyze the commits touching the given path (or git pathspec, like '*.rs'). Can be repeated to analyze multiple paths at once."
+    )]
+    path: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't resolve author identities through the repo's .mailmap file."
+    )]
+    no_mailmap: bool,
+
+    #[arg(
+        global = true,
+        long,
+        visible_alias = "count-co-authors",
+        help = "Also credit the people listed in the 'Co-authored-by' trailers of a commit. Each co-author gets the whole commit, as if they had authored it. The report then tells how many commits of each author were co-authored."
+    )]
+    credit_coauthors: bool,
+
+    #[arg(
+        global = true,
+        long,
+        requires = "credit_coauthors",
+        help = "Split the added and removed lines of a co-authored commit evenly between its author and co-authors, instead of giving each of them all of the lines."
+    )]
+    split_coauthored_lines: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Also count merge commits. Their lines aren't counted, since they were already credited to the commits being merged."
+    )]
+    include_merges: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Also count the files marked as 'linguist-vendored' or 'linguist-generated' in .gitattributes, which are skipped by default."
+    )]
+    no_respect_gitattributes: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't count the lines whose only change is whitespace, e.g. after reformatting or reindenting a file."
+    )]
+    ignore_whitespace: bool,
+
+    #[arg(
+        global = true,
+        long,
+        overrides_with = "no_follow_renames",
+        help = "Detect renamed files, so that moving a file doesn't credit whoever moved it with all of its lines. This is the default, even if 'diff.renames' is disabled in the git config."
+    )]
+    follow_renames: bool,
+
+    #[arg(
+        global = true,
+        long,
+        overrides_with = "follow_renames",
+        help = "Don't detect renamed files: a rename counts as removing every line of the old path and adding them back under the new one."
+    )]
+    no_follow_renames: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATTERN",
+        help = "Only report the authors whose name or email contains the given pattern, ignoring case. Unlike git's '--author', the percentages stay relative to the whole repo. Can be repeated."
+    )]
+    author: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATTERN",
+        help = "Don't report authors whose name or email contains the given text, ignoring case and parts of longer words. Wrap it in slashes to use a regular expression instead, e.g. '/^ci-.*$/'. Can be repeated."
+    )]
+    exclude_author: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        visible_alias = "exclude-path",
+        value_name = "GLOB",
+        help = "Don't count the lines of the files matching the given gitignore-like pattern, e.g. '**/*.lock'. A pattern starting with '!' includes the matching files again. Can be repeated."
+    )]
+    exclude: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "Read more '--exclude' patterns from the given file, one per line, like a .gitignore file."
+    )]
+    exclude_from: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't count the lines of lockfiles, dependencies and other commonly generated files, like 'Cargo.lock', 'node_modules/' or '*.min.js'."
+    )]
+    exclude_generated: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't report bots, like 'dependabot[bot]', 'Renovate Bot', 'github-actions' or any email containing '-bot@'."
+    )]
+    exclude_bots: bool,
+
+    #[arg(long, help = "Include the email of each author in the report.")]
+    show_emails: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "author",
+        help = "Replace each author with a pseudonym like 'author-001', numbered from the author with the most commits. The same history always gets the same pseudonyms, and the emails are redacted too."
+    )]
+    anonymize: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["author", "show_emails", "anonymize"],
+        help = "Report teams instead of individual authors. The TOML (or JSON, for '.json' files) file maps author names (or emails, which can be globs like '*@corp.com') to team names, e.g. '\"Jane Doe\" = \"Platform\"'. Authors that aren't listed are grouped under 'Unassigned'."
+    )]
+    teams: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["teams", "show_emails"],
+        help = "With '--group-by domain', a TOML (or JSON, for '.json' files) file that maps email domains to the names of their organizations, e.g. '\"corp.co.uk\" = \"Corp\"'. The domains that aren't listed are reported as they are."
+    )]
+    domain_map: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "How the ownership is measured: by going through the history, or by blaming the current content of the files (slower, only for the default report).",
+        value_enum,
+        default_value_t = Mode::Log
+    )]
+    mode: Mode,
+
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        help = "With '--mode blame', the maximum number of files blamed in parallel. If 0, the number of CPUs is used.",
+        default_value_t = 0
+    )]
+    jobs: usize,
+
+    #[arg(
+        short,
+        long,
+        help = "What the stats are computed for: each author, or each file (or directory) along with its top authors.",
+        value_enum,
+        default_value_t = GroupBy::Author
+    )]
+    group_by: GroupBy,
+
+    #[arg(
+        long,
+        conflicts_with = "group_by",
+        help = "Instead of the stats, report how many commits each author has made on each day of the week, in the timezone of the author. The text format draws them as bars when colors are enabled."
+    )]
+    weekday_histogram: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["group_by", "weekday_histogram"],
+        help = "Instead of the stats, list the files that were only ever changed by a single author, grouped by that author. They're the riskiest files when the author leaves."
+    )]
+    orphan_risk: bool,
+
+    #[arg(
+        long,
+        value_name = "PERIOD",
+        value_enum,
+        conflicts_with_all = ["group_by", "weekday_histogram", "orphan_risk"],
+        help = "Instead of the stats, report how many commits each author has made in each month, quarter or year, from the oldest commit to the newest one. The text format draws each author as a sparkline."
+    )]
+    timeline: Option<TimelinePeriod>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["group_by", "weekday_histogram", "orphan_risk", "timeline"],
+        help = "Instead of the stats, report when each author commits: how many commits they've made in each hour of the day and on each day of the week, in the timezone of the author."
+    )]
+    when: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["group_by", "weekday_histogram", "orphan_risk", "timeline", "when", "format", "output", "top", "totals"],
+        help = "Instead of printing a report, explore the authors in the terminal once the history is analyzed: move with the arrow keys, sort by a column by pressing its number, filter the authors by name with '/' and quit with 'q'. The selected author is detailed with their top files and activity."
+    )]
+    interactive: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "With '--group-by file' or '--group-by directory', the number of authors reported for each entry.",
+        default_value_t = 3
+    )]
+    authors_per_file: usize,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        help = "With '--group-by directory' (or 'codeowners' and 'bus-factor'), the number of leading path components files are grouped by, e.g. 2 groups 'crates/parser/src/lib.rs' under 'crates/parser'.",
+        default_value_t = 1
+    )]
+    depth: usize,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        help = "The format used to print the final stats to stdout. Defaults to 'table' when stdout is a terminal, 'text' otherwise.",
+        value_enum
+    )]
+    format: Option<Format>,
+
+    #[arg(
+        long,
+        help = "The field separator used by '--format csv'. Use '\\t' to get tab separated values.",
+        value_parser = parse_delimiter,
+        default_value = ","
+    )]
+    delimiter: u8,
+
+    #[arg(
+        long,
+        help = "With '--format markdown', print a title line mentioning the analyzed repo and branch before the table."
+    )]
+    markdown_title: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Never use colors, neither in the text and table output nor in the messages. Colors are also disabled by setting NO_COLOR, or when not writing to a terminal."
+    )]
+    no_color: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "The metric used to sort the authors, from the highest to the lowest (or alphabetically, for 'name'). Ties are sorted by author name.",
+        value_enum,
+        default_value_t = SortBy::Commits
+    )]
+    sort_by: SortBy,
+
+    #[arg(short, long, help = "Reverse the order used by '--sort-by'.")]
+    reverse: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Only print the first N authors (or files), after sorting. If 0 or not provided, everything is printed."
+    )]
+    top: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Don't report authors that have made less than N commits."
+    )]
+    min_commits: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Don't report authors that have changed (added or removed) less than N lines."
+    )]
+    min_lines: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "Also report a recency weighted score for each author, where each commit counts as 0.5^(age / DURATION). Accepts hours, days, weeks, months or years, e.g. '180d' or '2y'.",
+        value_parser = parse_half_life
+    )]
+    half_life: Option<HalfLife>,
+
+    #[arg(
+        long,
+        help = "Also report the totals of the whole repo with '--format json' (which then prints an object with 'authors' and 'totals') or '--format csv' (as a final 'Total' row). The text and table formats always end with them."
+    )]
+    totals: bool,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        value_name = "PATH",
+        help = "Write the report to the given file instead of stdout. Missing parent directories are created."
+    )]
+    output: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Don't draw any progress bar. They're only drawn when stderr is a terminal anyway."
+    )]
+    no_progress: bool,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        action = ArgAction::Count,
+        help = "Print more details on stderr, like the git commands being run and how long they took. Can be repeated for even more details. Defaults to the level in RUST_LOG, if set."
+    )]
+    verbose: u8,
+
+    #[arg(
+        global = true,
+        short,
+        long,
+        conflicts_with = "verbose",
+        help = "Only print warnings and errors on stderr."
+    )]
+    quiet: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PATH",
+        help = "Read the default value of the flags from the given TOML file, instead of the .whos-done-that.toml file of the target directory. The flags passed on the command line take precedence."
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Print the effective settings, merged from the config file and the command line, then exit."
+    )]
+    print_config: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Keep the commits read from the history of each repo in a cache, so that the next runs on the same revisions with the same options don't have to go through the history again. The cache is invalidated as soon as the revisions move."
+    )]
+    cache: bool,
+
+    #[arg(
+        global = true,
+        long,
+        overrides_with = "cache",
+        help = "Don't use the cache, e.g. when '--cache' is set in the config file."
+    )]
+    no_cache: bool,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Delete the cache of '--cache', then exit."
+    )]
+    clear_cache: bool,
+}
+
+impl Cli {
+    /// Return the range passed via '--range', or as the positional argument.
+    fn range(&self) -> Option<&str> {
+        self.range.as_deref().or(self.revspec.as_deref())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Generate a GitHub CODEOWNERS file, listing the top authors of each directory
+    Codeowners(CodeownersArgs),
+    /// Report the CODEOWNERS entries whose owners have (almost) never touched the code they own
+    CheckCodeowners(CheckCodeownersArgs),
+    /// Compute the bus factor of the repo and of each directory: the minimum number
+    /// of authors that, together, account for most of the changes
+    BusFactor(BusFactorArgs),
+    /// Compare the commits of each author between two time windows, to see who's
+    /// ramping up and who's winding down
+    Compare(CompareArgs),
+    /// Print the completion script of the given shell to stdout. Doesn't need a git repo
+    Completions(CompletionsArgs),
+}
+
+#[derive(Args, Debug)]
+struct CompareArgs {
+    #[arg(
+        long,
+        value_name = "FROM..TO",
+        value_parser = parse_date_window,
+        required_unless_present = "rev_a",
+        help = "The first time window, as 'YYYY-MM-DD..YYYY-MM-DD' where both dates are included. Either date can be left out, e.g. '..2023-12-31' for everything until the end of 2023."
+    )]
+    window_a: Option<DateWindow>,
+
+    #[arg(
+        long,
+        value_name = "FROM..TO",
+        value_parser = parse_date_window,
+        required_unless_present = "rev_a",
+        help = "The second time window, with the same syntax as '--window-a'."
+    )]
+    window_b: Option<DateWindow>,
+
+    #[arg(
+        long,
+        value_name = "REVISION",
+        conflicts_with_all = ["window_a", "window_b"],
+        requires = "rev_b",
+        help = "Instead of time windows, compare the history of a revision (e.g. 'v1.0') to the one of '--rev-b'."
+    )]
+    rev_a: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "REVISION",
+        conflicts_with_all = ["window_a", "window_b"],
+        requires = "rev_a",
+        help = "The revision whose history is compared to the one of '--rev-a', e.g. 'HEAD'."
+    )]
+    rev_b: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    #[arg(help = "The shell to generate the completions for.", value_enum)]
+    shell: Shell,
+}
+
+#[derive(Args, Debug)]
+struct BusFactorArgs {
+    #[arg(
+        long,
+        help = "The share of the changes that the authors must account for, either between 0 and 1 or as a percentage (e.g. '50%').",
+        value_parser = parse_threshold,
+        default_value = "50%"
+    )]
+    threshold: f64,
+
+    #[arg(
+        long,
+        help = "What the changes of each author are measured by.",
+        value_enum,
+        default_value_t = BusFactorMetric::Commits
+    )]
+    metric: BusFactorMetric,
+}
+
+/// The length of the periods of `--timeline`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TimelinePeriod {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BusFactorMetric {
+    /// The number of added lines
+    Additions,
+    /// The number of commits
+    Commits,
+}
+
+#[derive(Args, Debug)]
+struct CheckCodeownersArgs {
     #[arg(
-        short,
         long,
-        help = "The target directory to analyze. It must be a git repo. If not provided, the current directory will be used instead."
+        value_name = "PATH",
+        help = "The CODEOWNERS file to check. If not provided, it's searched in the same places GitHub does: '.github/', the root of the repo and 'docs/'."
     )]
-    target_dir: Option<PathBuf>,
+    codeowners: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "TOML file mapping author names (or emails) to GitHub handles, e.g. '\"Jane Doe\" = \"@janedoe\"'. Needed to check owners listed by handle, rather than by email."
+    )]
+    handle_map: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Report owners that have made less than N commits under the pattern they own. Combine with '--since' to only consider recent activity.",
+        default_value_t = 1
+    )]
+    min_commits: usize,
+}
+
+#[derive(Args, Debug)]
+struct CodeownersArgs {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "TOML file mapping author names (or emails) to GitHub handles, e.g. '\"Jane Doe\" = \"@janedoe\"'. Authors without a handle are left out."
+    )]
+    handle_map: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        help = "Only list the authors that changed at least this share of the lines of a directory.",
+        default_value_t = 20.0
+    )]
+    min_share: f64,
 
     #[arg(
-        short,
         long,
-        help = "Branch name used to search for commit authors.",
-        default_value = "main"
+        value_name = "N",
+        help = "The maximum number of owners listed for each directory.",
+        default_value_t = 2
     )]
-    branch: String,
+    max_owners: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Count the commits and the changed lines of each author
+    Log,
+    /// Count the lines of the branch that each author touched last, via `git blame`
+    Blame,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupBy {
+    /// One entry per author
+    Author,
+    /// One entry per file, sorted by the number of changed lines
+    File,
+    /// One entry per directory (see `--depth`), sorted by the number of changed lines
+    Directory,
+    /// One entry per email domain (see `--domain-map`), with the same stats as the authors
+    Domain,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// One human readable sentence per author
+    #[value(alias = "plain")]
+    Text,
+    /// Aligned columns with a header row
+    Table,
+    /// An array of objects, one per author
+    Json,
+    /// A header row followed by one row per author
+    Csv,
+    /// A GitHub flavored markdown table
+    Markdown,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SortBy {
+    /// Number of commits
+    Commits,
+    /// Number of added lines
+    Additions,
+    /// Number of removed lines
+    Removals,
+    /// Number of added lines minus number of removed lines
+    Net,
+    /// Author name, alphabetically
+    Name,
+    /// Recency weighted score, see `--half-life`
+    Score,
+    /// Number of distinct days with at least one commit
+    ActiveDays,
+    /// Date of the most recent commit, from the newest
+    Recency,
+}
+
+/// How long it takes for a commit to count half as much, see `--half-life`.
+#[derive(Clone, Debug)]
+struct HalfLife {
+    seconds: f64,
+    /// The duration as it was passed by the user, e.g. `180d`
+    text: String,
 }
 
-struct CodeEdits {
-    additions: usize,
-    removals: usize,
+/// A range of author dates, see `compare`. Both ends are included.
+#[derive(Serialize, Clone, Debug)]
+struct DateWindow {
+    /// As YYYY-MM-DD, `None` when the window is open on that side
+    from: Option<String>,
+    to: Option<String>,
 }
 
-struct AuthorData {
-    author_name: String,
-    code_edits: CodeEdits,
-    num_commits: usize,
+impl DateWindow {
+    /// Return true if `date` (as YYYY-MM-DD) is inside the window.
+    fn contains(&self, date: &str) -> bool {
+        // Dates in the same format can be compared as strings
+        self.from.as_deref().is_none_or(|from| date >= from)
+            && self.to.as_deref().is_none_or(|to| date <= to)
+    }
 }
 
-impl Display for CodeEdits {
+impl Display for DateWindow {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = format!(
-            "{} additions and {} removals",
-            self.additions, self.removals
-        );
-        write!(f, "{s}")
+        let from = self.from.as_deref().unwrap_or_default();
+        let to = self.to.as_deref().unwrap_or_default();
+        write!(f, "{from}..{to}")
     }
 }
 
 fn main() -> eyre::Result<()> {
     color_eyre::install()?;
 
-    let cli = Cli::parse();
     let cwd = std::env::current_dir()?;
+    let (cli, matches, config_keys) = parse_cli(&cwd)?;
+    if cli.print_config {
+        return print_config(&matches, &config_keys);
+    }
+    if cli.clear_cache {
+        return clear_cache();
+    }
+
+    match &cli.command {
+        Some(Commands::Completions(args)) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            // clap_complete panics on write errors, e.g. when piped into 'head'
+            let mut script = Vec::new();
+            clap_complete::generate(args.shell, &mut command, name, &mut script);
+            std::io::stdout().write_all(&script)?;
+            Ok(())
+        }
+        _ => analyze_and_report(&cli, cwd),
+    }
+}
+
+/// Analyze the target directories and write their reports, which is what all the
+/// subcommands (but the completions) do.
+fn analyze_and_report(cli: &Cli, cwd: PathBuf) -> eyre::Result<()> {
+    if cli.mode == Mode::Blame
+        && (cli.command.is_some()
+            || cli.group_by != GroupBy::Author
+            || cli.weekday_histogram
+            || cli.orphan_risk
+            || cli.timeline.is_some()
+            || cli.when)
+    {
+        return Err(eyre::eyre!(
+            "'--mode blame' only supports the default report"
+        ))
+        .with_suggestion(|| {
+            "Remove '--group-by', the other reports like '--timeline' and the subcommand, or use '--mode log'"
+        });
+    }
+    if cli.interactive && (cli.command.is_some() || cli.mode == Mode::Blame) {
+        return Err(eyre::eyre!(
+            "'--interactive' only supports the authors report built from the history"
+        ))
+        .with_suggestion(|| "Remove the subcommand and '--mode blame'");
+    }
+    // Checked before going through the history, which can take a while
+    if cli.interactive && !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+        return Err(eyre::eyre!("'--interactive' needs a terminal")).with_suggestion(|| {
+            "Run it from a terminal, or remove '--interactive' to print a report"
+        });
+    }
+    if cli.mode == Mode::Blame && (cli.all_branches || cli.range().is_some()) {
+        return Err(eyre::eyre!(
+            "'--mode blame' can only look at the files of a single branch"
+        ))
+        .with_suggestion(|| {
+            "Pick the branch via '--branch' instead of '--all-branches' or '--range'"
+        });
+    }
+
+    let mut target_dirs = match cli.target_dir.is_empty() {
+        _ if cli.stdin => read_target_dirs(std::io::stdin().lock())?,
+        true => vec![cwd],
+        false => cli.target_dir.clone(),
+    };
+    if cli.recursive {
+        let mut repos = Vec::new();
+        for target_dir in &target_dirs {
+            let mut found_repos = find_git_repos(target_dir, cli.max_depth)?;
+            tracing::info!(
+                "Found {} git repos under '{}'",
+                found_repos.len(),
+                target_dir.display()
+            );
+            repos.append(&mut found_repos);
+        }
+        if repos.is_empty() {
+            return Err(eyre::eyre!("No git repo found in the target directories"))
+                .with_suggestion(|| "Increase '--max-depth', or check the target directories");
+        }
+        target_dirs = repos;
+    }
+    let is_aggregated = cli.aggregate && target_dirs.len() > 1;
+    if cli.interactive && target_dirs.len() > 1 && !is_aggregated {
+        return Err(eyre::eyre!(
+            "'--interactive' can only explore a single report"
+        ))
+        .with_suggestion(|| {
+            "Pass '--aggregate' to combine the target directories in a single report"
+        });
+    }
+    if is_aggregated
+        && (cli.mode == Mode::Blame
+            || matches!(
+                cli.command,
+                Some(Commands::Codeowners(_) | Commands::CheckCodeowners(_))
+            ))
+    {
+        return Err(eyre::eyre!(
+            "'--aggregate' only supports the reports built from the history"
+        ))
+        .with_suggestion(|| "Remove '--aggregate' to get one report per repo");
+    }
+    if (cli.anonymize || cli.teams.is_some())
+        && (cli.mode == Mode::Blame
+            || matches!(
+                cli.command,
+                Some(Commands::Codeowners(_) | Commands::CheckCodeowners(_))
+            ))
+    {
+        return Err(eyre::eyre!(
+            "'--anonymize' and '--teams' only support the reports built from the history"
+        ))
+        .with_suggestion(|| "CODEOWNERS files and blames need the real identity of the authors");
+    }
+    if let Some(Commands::Compare(args)) = &cli.command {
+        if args.rev_a.is_some()
+            && (cli.branch.is_some() || cli.range().is_some() || cli.all_branches)
+        {
+            return Err(eyre::eyre!(
+                "'--rev-a' and '--rev-b' already pick the analyzed revisions"
+            ))
+            .with_suggestion(|| "Remove '--branch', '--all-branches' and the range");
+        }
+        // The history of '--rev-a' is read while writing the report, after these are applied
+        if args.rev_a.is_some() && (is_aggregated || cli.anonymize || cli.teams.is_some()) {
+            return Err(eyre::eyre!(
+                "'--rev-a' can't be combined with '--aggregate', '--anonymize' nor '--teams'"
+            ))
+            .with_suggestion(|| "Compare time windows via '--window-a' and '--window-b' instead");
+        }
+    }
+    let teams = cli.teams.as_deref().map(read_teams).transpose()?;
+    let is_domain_report = cli.command.is_none() && cli.group_by == GroupBy::Domain;
+    if is_domain_report && (cli.teams.is_some() || cli.show_emails) {
+        return Err(eyre::eyre!(
+            "'--group-by domain' can't be combined with '--teams' nor '--show-emails'"
+        ))
+        .with_suggestion(|| "Use '--domain-map' to gather the domains into organizations");
+    }
+    if cli.domain_map.is_some() && !is_domain_report {
+        return Err(eyre::eyre!("'--domain-map' requires '--group-by domain'"))
+            .with_suggestion(|| "Pass '--group-by domain' to report organizations");
+    }
+    let domain_map = cli.domain_map.as_deref().map(read_domain_map).transpose()?;
+    if target_dirs.len() > 1 && matches!(cli.command, Some(Commands::Codeowners(_))) {
+        return Err(eyre::eyre!(
+            "A CODEOWNERS file can only be generated for a single repo"
+        ))
+        .with_suggestion(|| "Pass a single '--target-dir'");
+    }
+
+    if cli.dry_run {
+        return print_log_commands(cli, &target_dirs);
+    }
+
+    // Print the final stats
+    let format = cli.format.unwrap_or_else(|| {
+        if cli.output.is_none() && std::io::stdout().is_terminal() {
+            Format::Table
+        } else {
+            Format::Text
+        }
+    });
+    if target_dirs.len() > 1 && !is_aggregated && format == Format::Csv {
+        return Err(eyre::eyre!(
+            "The CSV reports of multiple repos can't be written one after the other"
+        ))
+        .with_suggestion(|| "Pass '--aggregate' to combine them in a single report");
+    }
+
+    if !cli.path.is_empty() {
+        tracing::info!("Limiting the analysis to {}", cli.path.join(", "));
+    }
+
+    let excluded_paths = get_excluded_paths(
+        &cli.exclude,
+        cli.exclude_from.as_deref(),
+        cli.exclude_generated,
+    )?;
+    let exclude_patterns =
+        get_exclude_patterns(&cli.exclude_author, cli.exclude_bots).map_err(into_report)?;
+
+    let mut repos = Vec::new();
+    for target_dir in &target_dirs {
+        match analyze_repo(cli, target_dir, &excluded_paths, &exclude_patterns) {
+            Ok(repo) => repos.push(repo),
+            Err(err) if target_dirs.len() > 1 && !cli.fail_fast => {
+                tracing::error!("Skipping '{}': {err}", target_dir.display());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if repos.is_empty() {
+        return Err(eyre::eyre!(
+            "None of the target directories could be analyzed"
+        ))
+        .with_suggestion(|| "Pass '--fail-fast' to see the full error of the first one");
+    }
+    if is_aggregated {
+        repos = vec![aggregate_repos(repos)?];
+    }
+    if let Some(teams) = &teams {
+        repos
+            .iter_mut()
+            .for_each(|r| assign_teams(&mut r.commits, teams));
+    }
+    if is_domain_report {
+        let domain_map = domain_map.unwrap_or_default();
+        repos
+            .iter_mut()
+            .for_each(|r| assign_domains(&mut r.commits, &domain_map));
+    }
+    // After aggregating, so that the same author gets the same pseudonym in every repo
+    if cli.anonymize {
+        repos
+            .iter_mut()
+            .for_each(|r| anonymize_authors(&mut r.commits));
+    }
+
+    // A requested author without commits usually means there's a typo in the name
+    if cli.mode == Mode::Log {
+        for author in &cli.author {
+            let patterns = std::slice::from_ref(author);
+            if !repos
+                .iter()
+                .flat_map(|r| &r.commits)
+                .any(|c| is_author_requested(&c.author_name, &c.author_email, patterns))
+            {
+                tracing::warn!("No author matches '{author}'");
+            }
+        }
+    }
+
+    let palette = Palette {
+        enabled: !cli.no_color
+            && !is_no_color_set()
+            && cli.output.is_none()
+            && std::io::stdout().is_terminal(),
+    };
+
+    if cli.interactive {
+        let repo = &repos[0];
+        let title = format!(
+            "{} ({})",
+            repo.name,
+            repo.query_options.describe_revisions()
+        );
+        return tui::run(cli, title, &repo.commits, palette);
+    }
+
+    let mut writer: Box<dyn Write> = match &cli.output {
+        Some(path) => Box::new(std::io::BufWriter::new(create_output_file(path)?)),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout())),
+    };
+
+    // The JSON reports of multiple repos are combined in a single object, keyed by repo
+    let is_keyed_by_repo = repos.len() > 1 && format == Format::Json;
+    let mut reports_by_repo = serde_json::Map::new();
+
+    let mut num_stale_owners = 0;
+    let mut summaries = Vec::new();
+    for (i, repo) in repos.iter().enumerate() {
+        let title = cli.markdown_title.then(|| {
+            let mut title = format!(
+                "Ownership of {} ({})",
+                repo.name,
+                repo.query_options.describe_revisions()
+            );
+            if !repo.query_options.paths.is_empty() {
+                title.push_str(&format!(
+                    ", limited to {}",
+                    repo.query_options.paths.join(", ")
+                ));
+            }
+            title
+        });
+
+        // Each repo gets its own report, so they need a heading to be told apart
+        if repos.len() > 1 && !is_keyed_by_repo {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            match format {
+                Format::Markdown if title.is_none() => writeln!(writer, "## {}\n", repo.name)?,
+                Format::Markdown => {}
+                _ => writeln!(writer, "{}:", palette.author(&repo.name))?,
+            }
+        }
+
+        let report_options = ReportOptions {
+            format,
+            delimiter: cli.delimiter,
+            title,
+            palette,
+        };
+
+        let mut repo_report = Vec::new();
+        let mut repo_writer: &mut dyn Write = match is_keyed_by_repo {
+            true => &mut repo_report,
+            false => &mut writer,
+        };
+        let (summary, num_repo_stale_owners) = write_report(
+            &mut repo_writer,
+            cli,
+            &report_options,
+            repo,
+            &exclude_patterns,
+            &excluded_paths,
+        )?;
+        if is_keyed_by_repo {
+            let report = serde_json::from_slice(&repo_report)
+                .wrap_err_with(|| format!("Invalid JSON report for '{}'", repo.name))?;
+            reports_by_repo.insert(repo.name.clone(), report);
+        }
+        summaries.push(summary);
+        num_stale_owners += num_repo_stale_owners;
+    }
+    if is_keyed_by_repo {
+        serde_json::to_writer_pretty(&mut writer, &reports_by_repo)?;
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+
+    if let Some(path) = &cli.output {
+        tracing::info!(
+            "Wrote report for {} to {}",
+            summaries.join(", "),
+            path.display()
+        );
+    }
+
+    // So that CI jobs fail when CODEOWNERS gets stale
+    if num_stale_owners > 0 {
+        return Err(eyre::eyre!(
+            "Found {num_stale_owners} owners without enough commits in CODEOWNERS"
+        ))
+        .with_suggestion(|| "Update the entries listed above, or lower '--min-commits'");
+    }
+
+    Ok(())
+}
+
+/// Return true if colors were disabled via the NO_COLOR environment variable,
+/// see https://no-color.org
+fn is_no_color_set() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Return the target directories listed in `reader`, one per line, see `--stdin`.
+/// Blank lines are skipped.
+fn read_target_dirs(reader: impl BufRead) -> eyre::Result<Vec<PathBuf>> {
+    let mut target_dirs = Vec::new();
+    for line in reader.lines() {
+        let line = line.wrap_err("Failed to read the target directories from stdin")?;
+        let line = line.trim();
+        if !line.is_empty() {
+            target_dirs.push(PathBuf::from(line));
+        }
+    }
+
+    if target_dirs.is_empty() {
+        return Err(eyre::eyre!("No target directory was read from stdin")).with_suggestion(|| {
+            "Pass one path per line, e.g. 'ls -d ~/dev/* | whos-done-that --stdin'"
+        });
+    }
+
+    Ok(target_dirs)
+}
+
+/// The config file that is read from the target directory, unless `--config` is passed.
+const CONFIG_FILE_NAME: &str = ".whos-done-that.toml";
 
+/// The flags that can't be set from a config file.
+const NON_CONFIGURABLE_FLAGS: [&str; 7] = [
+    "config",
+    "print-config",
+    "clear-cache",
+    "verbose",
+    "quiet",
+    "help",
+    "version",
+];
+
+/// Print the messages on stderr, at the level picked with '--verbose' and '--quiet'.
+/// Without either of them, the level in `RUST_LOG` (e.g. `debug`) is used, if valid.
+fn init_tracing(cli: &Cli) -> eyre::Result<()> {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => tracing::Level::WARN,
+        (false, 0) => std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(tracing::Level::INFO),
+        (false, 1) => tracing::Level::DEBUG,
+        (false, _) => tracing::Level::TRACE,
+    };
     let subscriber = FmtSubscriber::builder()
         .with_target(false)
         .with_writer(std::io::stderr)
-        .with_max_level(tracing::Level::INFO)
+        .with_max_level(level)
+        .with_ansi(std::io::stderr().is_terminal() && !cli.no_color && !is_no_color_set())
         .without_time()
         .finish();
 
     tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+/// Parse the command line, using the values of the config file for the flags that weren't
+/// passed explicitly. Return the parsed flags, the raw matches and the keys read from the file.
+fn parse_cli(cwd: &Path) -> eyre::Result<(Cli, ArgMatches, Vec<String>)> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let matches = Cli::command().get_matches_from(&args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // Set up as soon as possible, so that the issues with the config file are reported
+    init_tracing(&cli)?;
+
+    let config_path = match &cli.config {
+        Some(path) => path.clone(),
+        None => cli
+            .target_dir
+            .first()
+            .map_or(cwd, PathBuf::as_path)
+            .join(CONFIG_FILE_NAME),
+    };
+    if cli.config.is_none() && !config_path.is_file() {
+        return Ok((cli, matches, Vec::new()));
+    }
+
+    let (config_args, config_keys) = read_config(&config_path, &matches)?;
+    if config_args.is_empty() {
+        return Ok((cli, matches, config_keys));
+    }
 
-    let target_dir = cli.target_dir.unwrap_or(cwd);
+    // The values of the config file go first, so that they're part of the top level command
+    args.splice(1..1, config_args.into_iter().map(OsString::from));
+    let matches = Cli::command()
+        .try_get_matches_from(&args)
+        .wrap_err_with(|| format!("Invalid settings in '{}'", config_path.display()))?;
+    let cli = Cli::from_arg_matches(&matches)?;
 
-    tracing::info!("Getting a list of authors..");
-    let authors = get_all_authors(&target_dir, &cli.branch)?;
+    Ok((cli, matches, config_keys))
+}
 
-    let mut authors_data = Vec::new();
+/// Turn the keys of the config file at `path` into command line flags, skipping the flags
+/// that were already passed on the command line. Keys mirror the long flags, e.g.
+/// `exclude-bots = true` or `exclude_author = ["Jane"]`. Unknown keys are only warned about.
+/// Return the flags, and the names of the flags that were taken from the file.
+fn read_config(path: &Path, matches: &ArgMatches) -> eyre::Result<(Vec<String>, Vec<String>)> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read the config file '{}'", path.display()))?;
+    let table: toml::Table = contents
+        .parse()
+        .wrap_err_with(|| format!("'{}' is not a valid TOML file", path.display()))?;
+    tracing::info!("Reading the settings from '{}'", path.display());
 
-    tracing::info!("Compiling stats..");
+    let command = Cli::command();
+    let mut args = Vec::new();
+    let mut keys = Vec::new();
+    for (key, value) in table {
+        let long = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .filter(|_| !NON_CONFIGURABLE_FLAGS.contains(&long.as_str()))
+            .find(|a| a.get_long() == Some(long.as_str()))
+        else {
+            tracing::warn!("Ignoring unknown key '{key}' in '{}'", path.display());
+            continue;
+        };
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
 
-    // TODO: This could be parallelized with rayon
-    for author in authors {
-        let num_commits = get_num_author_commits(&author, target_dir.as_path(), &cli.branch)?;
-        let code_edits = get_num_author_edits(&author, target_dir.as_path(), &cli.branch)?;
-        authors_data.push(AuthorData {
-            num_commits,
-            code_edits,
-            author_name: author.to_owned(),
-        });
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => args.push(format!("--{long}")),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(s) => args.push(format!("--{long}={s}")),
+                value => args.push(format!("--{long}={value}")),
+            }
+        }
+        keys.push(long);
     }
-    authors_data.sort_by(|a, b| a.num_commits.cmp(&b.num_commits).reverse());
 
-    // Print the final stats
-    let stdout = std::io::stdout();
-    let mut stdout_handle = std::io::BufWriter::new(stdout);
+    Ok((args, keys))
+}
 
-    for author_data in authors_data {
-        let ending = match author_data.num_commits {
-            0 => {
-                continue;
-            }
-            1 => "1 commit".to_string(),
-            _ => format!("{} commits", author_data.num_commits),
+/// Print the value of every flag as TOML, with where it comes from.
+fn print_config(matches: &ArgMatches, config_keys: &[String]) -> eyre::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
         };
+        if NON_CONFIGURABLE_FLAGS.contains(&long) {
+            continue;
+        }
 
-        writeln!(
-            stdout_handle,
-            "{} has made {ending}: {}",
-            author_data.author_name, author_data.code_edits
-        )?;
+        let source = match matches.value_source(id) {
+            _ if config_keys.iter().any(|k| k == long) => "config file",
+            Some(ValueSource::CommandLine) => "command line",
+            Some(ValueSource::DefaultValue) => "default",
+            Some(_) => "environment",
+            None => continue,
+        };
+        let value = if matches!(arg.get_action(), ArgAction::SetTrue) {
+            toml::Value::Boolean(matches.get_flag(id))
+        } else {
+            let values: Vec<toml::Value> = matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|v| {
+                    let v = v.to_string_lossy();
+                    // So that numbers can be copied into a config file as they are
+                    v.parse::<i64>()
+                        .map(toml::Value::Integer)
+                        .or_else(|_| v.parse::<f64>().map(toml::Value::Float))
+                        .unwrap_or_else(|_| toml::Value::String(v.into_owned()))
+                })
+                .collect();
+            match (arg.get_action(), values.len()) {
+                (ArgAction::Append, _) => toml::Value::Array(values),
+                (_, 1) => values[0].clone(),
+                _ => continue,
+            }
+        };
+        writeln!(stdout, "{long} = {value} # {source}")?;
     }
 
     Ok(())
 }
 
-/// Return a list sorted lexicographically (by byte values) containing
-/// all of the detected authors for the git repository living at `target_dir`.
-/// For more notes on the sorting, see:
-/// https://doc.rust-lang.org/std/primitive.str.html#impl-Ord
-fn get_all_authors(target_dir: impl AsRef<Path>, branch_name: &str) -> eyre::Result<Vec<String>> {
-    let b = String::from_utf8(Bash::quote_vec(branch_name))?;
-    let command = format!(
-        "git -C {} shortlog --summary --numbered --no-merges --all --branches={b}",
-        target_dir.as_ref().display()
-    );
-    let stdout = get_stdout_from_subprocess_or_fail(&command)?;
-
-    let mut authors = stdout
-        .lines()
-        .filter_map(|l| {
-            let author_tokens = l
-                .split_ascii_whitespace()
-                .enumerate()
-                .filter(|(i, _)| *i != 0)
-                .map(|(_, t)| t)
-                .collect::<Vec<&str>>();
-
-            Some(author_tokens.join(" "))
-        })
-        .map(|a| a.to_string())
-        .collect::<Vec<String>>();
-
-    authors.sort();
-
-    Ok(authors)
-}
-
-/// Return the number of commits authored by the given `author`
-/// for the git repository living at `target_dir`.
-fn get_num_author_commits(
-    author: &str,
-    target_dir: impl AsRef<Path>,
-    branch_name: &str,
-) -> eyre::Result<usize> {
-    let a = String::from_utf8(Bash::quote_vec(author))?;
-    let b = String::from_utf8(Bash::quote_vec(branch_name))?;
-
-    let command = format!(
-        "git -C {} rev-list HEAD --author={a} --count --branches={b}",
-        target_dir.as_ref().display(),
-    );
-    let stdout = get_stdout_from_subprocess_or_fail(&command)?;
+/// The commits of a repo, ready to be reported.
+struct AnalyzedRepo {
+    /// The target directory as it was passed, or the directories of all of the aggregated repos
+    name: String,
+    target_dir: PathBuf,
+    query_options: QueryOptions,
+    commits: Vec<Commit>,
+}
 
-    let num_of_commits = stdout.parse().with_suggestion(|| {
-        format!("Failed to run '{command}'. '--count' didn't return a number!")
-    })?;
+/// Turn an error of the library into a report, along with a suggestion on how to fix it.
+fn into_report(error: whos_done_that::Error) -> eyre::Report {
+    let suggestion = match &error {
+        whos_done_that::Error::NotAGitRepo(_) => {
+            "Pass the path of a git repo via '--target-dir', or run this command inside one"
+        }
+        whos_done_that::Error::UnknownRevision(_) => {
+            "Check the spelling of '--branch' or '--range', or list the branches via 'git branch -a'"
+        }
+        whos_done_that::Error::InvalidPattern { .. } => {
+            "Special characters like '[' or '(' must be escaped with '\\' between the slashes, or drop the slashes to match the text as it is"
+        }
+        _ => return eyre::Report::new(error),
+    };
 
-    Ok(num_of_commits)
+    eyre::Report::new(error).with_suggestion(|| suggestion)
 }
 
-/// Return the number of edits authored by the given `author`
-/// for the git repository living at `target_dir`.
-fn get_num_author_edits(
-    author: &str,
-    target_dir: impl AsRef<Path>,
-    branch_name: &str,
-) -> eyre::Result<CodeEdits> {
-    let a = String::from_utf8(Bash::quote_vec(author))?;
-    let b = String::from_utf8(Bash::quote_vec(branch_name))?;
+/// Go through the history of the repo at `target_dir`, keeping the commits and the paths
+/// requested in `cli`. In blame mode, only the options are resolved.
+fn analyze_repo(
+    cli: &Cli,
+    target_dir: &Path,
+    excluded_paths: &[PathPattern],
+    exclude_patterns: &[Regex],
+) -> eyre::Result<AnalyzedRepo> {
+    ensure_is_git_repo(target_dir).map_err(into_report)?;
 
-    let command = format!(
-        "git -C {} log --author={a} --numstat --pretty=tformat: --branches={b} --all",
-        target_dir.as_ref().display()
-    );
-    let stdout = get_stdout_from_subprocess_or_fail(&command)?;
+    let query_options = get_query_options(cli, target_dir);
+    let commits = collect_commits(
+        cli,
+        target_dir,
+        &query_options,
+        excluded_paths,
+        exclude_patterns,
+    )?;
 
-    let mut total_additions = 0;
-    let mut total_removals = 0;
+    Ok(AnalyzedRepo {
+        name: target_dir.display().to_string(),
+        target_dir: target_dir.to_path_buf(),
+        query_options,
+        commits,
+    })
+}
 
-    stdout.lines().for_each(|l| {
-        let tokens: Vec<&str> = l.split_ascii_whitespace().collect();
+/// Return the commits of the repo at `target_dir` that match `query_options`,
+/// without the excluded paths and authors.
+fn collect_commits(
+    cli: &Cli,
+    target_dir: &Path,
+    query_options: &QueryOptions,
+    excluded_paths: &[PathPattern],
+    exclude_patterns: &[Regex],
+) -> eyre::Result<Vec<Commit>> {
+    // Blaming doesn't need the history: the files are read directly
+    let mut commits = match cli.mode {
+        Mode::Log => {
+            let cache_entry = match cli.cache && !cli.no_cache {
+                true => Some(get_cache_entry(target_dir, query_options)?),
+                false => None,
+            };
+            let cached_commits = cache_entry.as_ref().and_then(CacheEntry::read);
 
-        if let Some(additions) = tokens.get(0) {
-            total_additions += additions.parse().unwrap_or(0);
+            let mut commits = match cached_commits {
+                Some(commits) => {
+                    tracing::info!("Using the cached commits of '{}'", target_dir.display());
+                    commits
+                }
+                None => {
+                    tracing::info!("Compiling stats for '{}'..", target_dir.display());
+                    let progress_bar = create_progress_bar(cli);
+                    let commits = get_commits(target_dir, query_options, |commit| {
+                        progress_bar.inc(1);
+                        progress_bar.set_message(commit.author_name.clone());
+                    })
+                    .map_err(into_report)?;
+                    progress_bar.finish_and_clear();
+                    if let Some(cache_entry) = &cache_entry {
+                        cache_entry.write(&commits)?;
+                    }
+                    commits
+                }
+            };
+            if cli.credit_coauthors {
+                commits = credit_coauthors(commits, cli.split_coauthored_lines);
+            }
+            commits
         }
-        if let Some(removals) = tokens.get(1) {
-            total_removals += removals.parse().unwrap_or(0);
+        Mode::Blame => Vec::new(),
+    };
+
+    if !excluded_paths.is_empty() {
+        // A commit that only touched excluded files (e.g. a bump of Cargo.lock) doesn't count,
+        // while the ones without any change to begin with (e.g. merges) are kept
+        commits.retain_mut(|commit| {
+            let had_changes = !commit.file_changes.is_empty();
+            commit
+                .file_changes
+                .retain(|f| !is_path_excluded(&f.path, excluded_paths));
+            !had_changes || !commit.file_changes.is_empty()
+        });
+    }
+
+    let mut excluded_authors = Vec::new();
+    commits.retain(|c| {
+        let excluded = is_author_excluded(&c.author_name, &c.author_email, exclude_patterns);
+        if excluded && !excluded_authors.contains(&c.author_email) {
+            tracing::debug!("Excluding {} <{}>", c.author_name, c.author_email);
+            excluded_authors.push(c.author_email.clone());
         }
+        !excluded
     });
 
-    let ce = CodeEdits {
-        additions: total_additions,
-        removals: total_removals,
+    // The default report filters the requested authors at the very end instead,
+    // so that their stats are still relative to the whole repo
+    let is_author_report = cli.command.is_none()
+        && cli.group_by == GroupBy::Author
+        && !cli.weekday_histogram
+        && !cli.orphan_risk
+        && cli.timeline.is_none()
+        && !cli.when;
+    if !is_author_report {
+        commits.retain(|c| is_author_requested(&c.author_name, &c.author_email, &cli.author));
+    }
+
+    Ok(commits)
+}
+
+/// Print the `git log` command that would go through the history of each of `target_dirs`,
+/// on stderr so that the report that would be written to stdout isn't mistaken for it.
+fn print_log_commands(cli: &Cli, target_dirs: &[PathBuf]) -> eyre::Result<()> {
+    if cli.mode == Mode::Blame {
+        return Err(eyre::eyre!("'--dry-run' only supports '--mode log'"))
+            .with_suggestion(|| "Blaming runs one git command per file, pass '-v' to see them");
+    }
+
+    for target_dir in target_dirs {
+        ensure_is_git_repo(target_dir).map_err(into_report)?;
+        let query_options = get_query_options(cli, target_dir);
+        eprintln!("{}", describe_log_command(target_dir, &query_options));
+    }
+
+    Ok(())
+}
+
+/// Return the options of the analysis of the repo at `target_dir` that were requested via `cli`.
+/// Without '--branch' nor a range, the default branch of the repo is detected.
+fn get_query_options(cli: &Cli, target_dir: &Path) -> QueryOptions {
+    let compared_revision = match &cli.command {
+        Some(Commands::Compare(args)) => args.rev_b.as_deref(),
+        _ => None,
+    };
+
+    QueryOptions {
+        branch_name: match (cli.range(), &cli.branch) {
+            _ if compared_revision.is_some() => compared_revision.unwrap_or_default().to_string(),
+            (Some(range), _) => range.to_string(),
+            (None, Some(branch)) => branch.clone(),
+            (None, None) => detect_default_branch(target_dir),
+        },
+        all_branches: cli.all_branches,
+        include_merges: cli.include_merges,
+        since: cli.since.clone(),
+        until: cli.until.clone(),
+        paths: cli.path.clone(),
+        use_mailmap: !cli.no_mailmap,
+        respect_gitattributes: !cli.no_respect_gitattributes,
+        ignore_whitespace: cli.ignore_whitespace,
+        follow_renames: !cli.no_follow_renames,
+        jobs: cli.jobs,
+    }
+}
+
+/// Combine the commits of `repos` into a single repo, where the authors are matched by email.
+/// The paths are prefixed with the name of the directory of their repo, so that they don't clash.
+fn aggregate_repos(repos: Vec<AnalyzedRepo>) -> eyre::Result<AnalyzedRepo> {
+    let name = repos
+        .iter()
+        .map(|r| r.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut commits = Vec::new();
+    let mut first_repo = None;
+
+    for mut repo in repos {
+        let prefix = repo
+            .target_dir
+            .canonicalize()
+            .ok()
+            .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| repo.name.clone());
+        for file_change in repo.commits.iter_mut().flat_map(|c| &mut c.file_changes) {
+            file_change.path = format!("{prefix}/{}", file_change.path);
+            if let Some(renamed_from) = &mut file_change.renamed_from {
+                *renamed_from = format!("{prefix}/{renamed_from}");
+            }
+        }
+        commits.append(&mut repo.commits);
+        first_repo.get_or_insert(repo);
+    }
+
+    // The options only differ by the detected branch, which is only used for the titles
+    let first_repo = first_repo.ok_or_else(|| eyre::eyre!("There are no repos to aggregate"))?;
+    Ok(AnalyzedRepo {
+        name,
+        commits,
+        ..first_repo
+    })
+}
+
+/// Write the report requested in `cli` for `repo` to `writer`.
+/// Return a summary of what was written, and the number of stale CODEOWNERS entries.
+fn write_report(
+    writer: &mut impl Write,
+    cli: &Cli,
+    report_options: &ReportOptions,
+    repo: &AnalyzedRepo,
+    exclude_patterns: &[Regex],
+    excluded_paths: &[PathPattern],
+) -> eyre::Result<(String, usize)> {
+    let commits = &repo.commits;
+    let mut num_stale_owners = 0;
+    let summary = match (&cli.command, cli.group_by) {
+        (Some(Commands::Codeowners(args)), _) => {
+            let num_rules = write_codeowners(writer, cli, args, commits, &repo.query_options)?;
+            format!("{num_rules} CODEOWNERS rules")
+        }
+        (Some(Commands::BusFactor(args)), _) => {
+            let num_directories =
+                write_bus_factor_report(writer, cli, args, report_options, commits)?;
+            format!("{num_directories} directories")
+        }
+        (Some(Commands::Compare(args)), _) => {
+            // The history of '--rev-b' is the one that was analyzed, like a branch would be
+            let sides = match (&args.rev_a, &args.window_a, &args.window_b) {
+                (Some(rev_a), _, _) => {
+                    let query_options = QueryOptions {
+                        branch_name: rev_a.clone(),
+                        ..repo.query_options.clone()
+                    };
+                    let commits_a = collect_commits(
+                        cli,
+                        &repo.target_dir,
+                        &query_options,
+                        excluded_paths,
+                        exclude_patterns,
+                    )?;
+                    CompareSides::Revisions {
+                        commits_a,
+                        rev_a,
+                        rev_b: &repo.query_options.branch_name,
+                    }
+                }
+                (None, Some(window_a), Some(window_b)) => {
+                    CompareSides::Windows { window_a, window_b }
+                }
+                _ => unreachable!("clap requires either both windows or both revisions"),
+            };
+            let num_authors = write_compare_report(writer, cli, &sides, report_options, commits)?;
+            format!("{num_authors} authors")
+        }
+        (Some(Commands::CheckCodeowners(args)), _) => {
+            num_stale_owners = write_codeowners_check(writer, &repo.target_dir, args, commits)?;
+            format!("{num_stale_owners} stale CODEOWNERS entries")
+        }
+        (Some(Commands::Completions(_)), _) => {
+            unreachable!("the completions are printed before analyzing anything")
+        }
+        (None, _) if cli.when => {
+            let num_authors = write_when_report(writer, cli, report_options, commits)?;
+            format!("{num_authors} authors")
+        }
+        (None, _) if cli.timeline.is_some() => {
+            let num_authors = write_timeline_report(writer, cli, report_options, commits)?;
+            format!("{num_authors} authors")
+        }
+        (None, _) if cli.orphan_risk => {
+            let num_files = write_orphan_risk_report(writer, cli, report_options, commits)?;
+            format!("{num_files} files with a single author")
+        }
+        (None, _) if cli.weekday_histogram => {
+            let num_authors = write_weekday_report(writer, cli, report_options, commits)?;
+            format!("{num_authors} authors")
+        }
+        (None, _) if cli.mode == Mode::Blame => {
+            let num_authors = write_blame_report(
+                writer,
+                cli,
+                report_options,
+                &repo.target_dir,
+                &repo.query_options,
+                exclude_patterns,
+                excluded_paths,
+            )?;
+            format!("{num_authors} authors")
+        }
+        (None, GroupBy::Author) => {
+            let num_authors = write_author_report(writer, cli, report_options, commits)?;
+            format!("{num_authors} authors")
+        }
+        (None, GroupBy::Domain) => {
+            let num_domains = write_author_report(writer, cli, report_options, commits)?;
+            format!("{num_domains} domains")
+        }
+        (None, GroupBy::File) => {
+            let num_files = write_file_report(writer, cli, report_options, commits)?;
+            format!("{num_files} files")
+        }
+        (None, GroupBy::Directory) => {
+            let num_directories = write_directory_report(writer, cli, report_options, commits)?;
+            format!("{num_directories} directories")
+        }
+    };
+
+    Ok((summary, num_stale_owners))
+}
+
+/// Return the git repos found under `root`, which is a repo itself if it contains `.git`.
+/// The insides of the repos aren't searched, so submodules aren't counted twice,
+/// and neither are hidden directories, like `.cargo`.
+fn find_git_repos(root: &Path, max_depth: Option<usize>) -> eyre::Result<Vec<PathBuf>> {
+    let mut repos = Vec::new();
+    let mut directories = vec![(root.to_path_buf(), 0)];
+
+    while let Some((directory, depth)) = directories.pop() {
+        if directory.join(".git").exists() {
+            repos.push(directory);
+            continue;
+        }
+        if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+
+        let entries = std::fs::read_dir(&directory)
+            .wrap_err_with(|| format!("Failed to list '{}'", directory.display()))?;
+        for entry in entries.filter_map(Result::ok) {
+            // Symbolic links are skipped, since they could lead to a loop
+            let is_directory = entry.file_type().is_ok_and(|t| t.is_dir());
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if is_directory && !is_hidden {
+                directories.push((entry.path(), depth + 1));
+            }
+        }
+    }
+
+    repos.sort();
+    Ok(repos)
+}
+
+/// Return a spinner that shows how many commits have been parsed so far,
+/// and the author of the last one. It's drawn on stderr, so that it doesn't
+/// end up in the report, and only if stderr is a terminal.
+fn create_progress_bar(cli: &Cli) -> ProgressBar {
+    if cli.no_progress || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let progress_bar = ProgressBar::new_spinner();
+    progress_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner} Parsed {human_pos} commits ({elapsed}), last by {msg}",
+        )
+        .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    progress_bar.enable_steady_tick(std::time::Duration::from_millis(100));
+
+    progress_bar
+}
+
+/// Options that control how a report is written.
+struct ReportOptions {
+    format: Format,
+    delimiter: u8,
+    /// Heading of the markdown output, if any
+    title: Option<String>,
+    /// Only used by the text and table formats
+    palette: Palette,
+}
+
+/// Parse the value passed to `--delimiter`: either a single ASCII character
+/// or the `\t` escape sequence, since typing a literal tab in a shell is a pain.
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "\\t" => Ok(b'\t'),
+        v if v.len() == 1 && v.is_ascii() => Ok(v.as_bytes()[0]),
+        v => Err(format!(
+            "'{v}' is not a valid delimiter, it must be a single ASCII character"
+        )),
+    }
+}
+
+/// Parse the value passed to `--threshold`, which must be in the `(0, 1]` range.
+/// Percentages (e.g. `50%`, or `50` since it's a whole number above 1) are converted to the same range.
+fn parse_threshold(value: &str) -> Result<f64, String> {
+    let threshold = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok().map(|p| p / 100.0),
+        // A value like '1.5' is most likely a typo, rather than 1.5%
+        None => value.parse::<f64>().ok().and_then(|t| match t {
+            t if t <= 1.0 => Some(t),
+            t if t.fract() == 0.0 => Some(t / 100.0),
+            _ => None,
+        }),
+    };
+
+    match threshold {
+        Some(t) if t > 0.0 && t <= 1.0 => Ok(t),
+        _ => Err(format!(
+            "'{value}' is not a valid threshold, it must be a number between 0 and 1 (e.g. 0.8) or a percentage (e.g. 80%)"
+        )),
+    }
+}
+
+/// Parse the value passed to `--window-a` or `--window-b`: two optional YYYY-MM-DD dates
+/// separated by `..`, e.g. `2024-01-01..2024-06-30` or `2024-01-01..`.
+fn parse_date_window(value: &str) -> Result<DateWindow, String> {
+    let error =
+        || format!("'{value}' is not a valid window, it must look like '2024-01-01..2024-06-30'");
+    let (from, to) = value.split_once("..").ok_or_else(error)?;
+
+    let is_date = |date: &str| {
+        let parts: Vec<&str> = date.split('-').collect();
+        matches!(parts.as_slice(), [y, m, d] if y.len() == 4 && m.len() == 2 && d.len() == 2)
+            && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()))
+    };
+    let parse_date = |date: &str| match date.trim() {
+        "" => Ok(None),
+        date if is_date(date) => Ok(Some(date.to_string())),
+        _ => Err(error()),
     };
 
-    Ok(ce)
+    Ok(DateWindow {
+        from: parse_date(from)?,
+        to: parse_date(to)?,
+    })
 }
 
-/// Run the given `command` in a bash subshell and return back `stdout` if
-/// it exited with 0. If the command exited with non 0 this will return an error
-/// and prints `stderr`.
-fn get_stdout_from_subprocess_or_fail(command: &str) -> eyre::Result<String> {
-    use std::process::Command;
+/// Parse the value passed to `--half-life`: a positive number followed by a unit,
+/// which can be `h` (hours), `d` (days), `w` (weeks), `m` (months of 30 days) or `y` (years of 365 days).
+/// A number without a unit is in days.
+fn parse_half_life(value: &str) -> Result<HalfLife, String> {
+    const DAY: f64 = 24.0 * 60.0 * 60.0;
+    let (number, unit_seconds) = match value.char_indices().last() {
+        Some((i, 'h')) => (&value[..i], DAY / 24.0),
+        Some((i, 'd')) => (&value[..i], DAY),
+        Some((i, 'w')) => (&value[..i], 7.0 * DAY),
+        Some((i, 'm')) => (&value[..i], 30.0 * DAY),
+        Some((i, 'y')) => (&value[..i], 365.0 * DAY),
+        _ => (value, DAY),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 && n.is_finite() => Ok(HalfLife {
+            seconds: n * unit_seconds,
+            text: value.to_string(),
+        }),
+        _ => Err(format!(
+            "'{value}' is not a valid duration, it should look like '180d', '6m' or '2y'"
+        )),
+    }
+}
 
-    log::debug!("Running '{command}'");
-    let subprocess_result = Command::new("bash").args(["-c", command]).output()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !subprocess_result.status.success() {
-        let stderr = String::from_utf8(subprocess_result.stderr).unwrap_or_default();
-        log::warn!("stderr from subprocess: {stderr}");
-        eyre::bail!("Failed to run '{command}'");
+    #[test]
+    fn parse_threshold_accepts_fractions_and_percentages() {
+        assert_eq!(parse_threshold("0.8"), Ok(0.8));
+        assert_eq!(parse_threshold("1"), Ok(1.0));
+        assert_eq!(parse_threshold("50%"), Ok(0.5));
+        assert_eq!(parse_threshold("50"), Ok(0.5));
+        assert_eq!(parse_threshold("12.5%"), Ok(0.125));
+
+        assert!(parse_threshold("0").is_err());
+        assert!(parse_threshold("-0.5").is_err());
+        assert!(parse_threshold("150%").is_err());
+        assert!(parse_threshold("150").is_err());
+        assert!(parse_threshold("1.5").is_err());
+        assert!(parse_threshold("80.5").is_err());
+        assert!(parse_threshold("half").is_err());
     }
 
-    let mut stdout = String::from_utf8(subprocess_result.stdout).unwrap_or_default();
-    if stdout.ends_with('\n') {
-        stdout.pop();
+    #[test]
+    fn parse_half_life_reads_the_unit() {
+        const DAY: f64 = 24.0 * 60.0 * 60.0;
+        let seconds = |value: &str| parse_half_life(value).map(|h| h.seconds);
+
+        assert_eq!(seconds("12h"), Ok(DAY / 2.0));
+        assert_eq!(seconds("180d"), Ok(180.0 * DAY));
+        assert_eq!(seconds("2w"), Ok(14.0 * DAY));
+        assert_eq!(seconds("6m"), Ok(180.0 * DAY));
+        assert_eq!(seconds("1y"), Ok(365.0 * DAY));
+        assert_eq!(seconds("90"), Ok(90.0 * DAY));
+        assert_eq!(parse_half_life("6m").unwrap().text, "6m");
+
+        assert!(parse_half_life("0d").is_err());
+        assert!(parse_half_life("-1y").is_err());
+        assert!(parse_half_life("d").is_err());
+        assert!(parse_half_life("forever").is_err());
     }
 
-    Ok(stdout.to_string())
+    #[test]
+    fn aggregate_repos_fails_without_any_repo() {
+        assert!(aggregate_repos(Vec::new()).is_err());
+    }
 }
@@ -0,0 +1,309 @@
+//! The building blocks shared by the reports: the colors, the tables (aligned, CSV or
+//! markdown) and the message about the entries hidden by `--top`.
+
+use std::io::Write;
+use std::path::Path;
+
+use color_eyre::{eyre, eyre::WrapErr, Section};
+use owo_colors::{OwoColorize, Style};
+use serde::Serialize;
+
+use whos_done_that::CodeEdits;
+
+use crate::Format;
+
+/// Colors used to make the human readable formats easier to scan.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub enabled: bool,
+}
+
+impl Palette {
+    /// Return `text` with the given `style`, or untouched if colors are disabled.
+    pub fn paint(&self, text: &str, style: Style) -> String {
+        if self.enabled {
+            text.style(style).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn author(&self, name: &str) -> String {
+        self.paint(name, Style::new().bold())
+    }
+
+    /// Return the same text as the `Display` implementation of `code_edits`, but colored.
+    pub fn code_edits(&self, code_edits: &CodeEdits) -> String {
+        let additions = format!("{} additions", code_edits.additions);
+        let removals = format!("{} removals", code_edits.removals);
+        format!(
+            "{} and {}{}",
+            self.paint(&additions, Style::new().green()),
+            self.paint(&removals, Style::new().red()),
+            code_edits.binary_changes_suffix()
+        )
+    }
+}
+
+/// Let the user know that `num_hidden` entries (e.g. `authors`) were hidden by `--top`:
+/// in the report itself for the human readable formats, otherwise via the logs.
+pub fn write_hidden_entries_message(
+    writer: &mut impl Write,
+    format: Format,
+    num_hidden: usize,
+    entries_name: &str,
+) -> eyre::Result<()> {
+    if num_hidden == 0 {
+        return Ok(());
+    }
+
+    let message = format!("…and {num_hidden} more {entries_name} (use --top 0 to show all)");
+    match format {
+        // Don't break the structured formats
+        Format::Text | Format::Table => writeln!(writer, "{message}")?,
+        Format::Json | Format::Csv | Format::Markdown => tracing::info!("{message}"),
+    }
+
+    Ok(())
+}
+
+/// Create (or truncate) the file at `path` that will contain the report,
+/// creating its parent directories if they don't exist yet.
+pub fn create_output_file(path: &Path) -> eyre::Result<std::fs::File> {
+    let error_message = || format!("Failed to create the output file '{}'", path.display());
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).wrap_err_with(error_message)?;
+    }
+
+    let file = std::fs::File::create(path)
+        .wrap_err_with(error_message)
+        .with_suggestion(|| "Check that the path is writable by the current user")?;
+
+    Ok(file)
+}
+
+/// Return `name`, followed by `email` if it's available.
+pub fn display_author(name: &str, email: Option<&str>) -> String {
+    match email {
+        Some(email) => format!("{name} <{email}>"),
+        None => name.to_string(),
+    }
+}
+
+/// Return `days` as a short human readable duration, e.g. `1 day` or `2.5 years`.
+pub fn format_days(days: usize) -> String {
+    match days {
+        1 => "1 day".to_string(),
+        0..365 => format!("{days} days"),
+        _ => format!("{:.1} years", days as f64 / 365.25),
+    }
+}
+
+/// The characters of the sparklines of `--timeline` and `--when`, from the lowest to the highest.
+const SPARKLINE_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Return `counts` as a sparkline, scaled to the highest of them.
+/// Periods without any commit are blank, so that they stand out from quiet ones.
+pub fn get_sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or_default().max(1);
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            _ => SPARKLINE_LEVELS[(count * SPARKLINE_LEVELS.len()).div_ceil(max) - 1],
+        })
+        .collect()
+}
+
+/// Names longer than this are truncated in the table output,
+/// so that a single long name doesn't break the alignment of the columns.
+pub const MAX_TABLE_AUTHOR_WIDTH: usize = 32;
+
+/// Write `header` and `rows` to `writer` as a table with aligned columns, where the
+/// first column holds the authors (truncated, so that they don't push the numbers away)
+/// and all of the others are numbers.
+pub fn write_author_table(
+    writer: &mut impl Write,
+    header: &[&str],
+    mut rows: Vec<Vec<String>>,
+    palette: Palette,
+) -> eyre::Result<()> {
+    for row in &mut rows {
+        row[0] = truncate_with_ellipsis(&row[0], MAX_TABLE_AUTHOR_WIDTH);
+    }
+    write_aligned_table(writer, header, &rows, 1, &[Style::new().bold()], palette)
+}
+
+/// Write `header` and `rows` to `writer` as a table with aligned columns.
+/// The first `num_text_columns` columns are aligned to the left,
+/// while all of the others (usually numbers) are aligned to the right.
+/// The cells of each row (but not the header) are painted with the matching
+/// entry of `styles`, if any.
+pub fn write_aligned_table(
+    writer: &mut impl Write,
+    header: &[&str],
+    rows: &[Vec<String>],
+    num_text_columns: usize,
+    styles: &[Style],
+    palette: Palette,
+) -> eyre::Result<()> {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    for (row_index, row) in std::iter::once(&header).chain(rows).enumerate() {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| {
+                // The padding is computed on the plain text, since escape codes take no space
+                let cell = if i < num_text_columns {
+                    format!("{cell:<width$}")
+                } else {
+                    format!("{cell:>width$}")
+                };
+                match styles.get(i) {
+                    Some(&style) if row_index > 0 => palette.paint(&cell, style),
+                    _ => cell,
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("  ");
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// Return `s` truncated to at most `max_chars` characters,
+/// replacing the last visible character with an ellipsis when needed.
+pub fn truncate_with_ellipsis(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+
+    let mut truncated: String = s.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Write `entries` to `writer` as a JSON array of objects.
+/// An empty slice still produces a valid (empty) array.
+pub fn write_json_report(writer: &mut impl Write, entries: &[impl Serialize]) -> eyre::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, entries)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+/// Write `header`, then each of `records`, to `writer` as CSV,
+/// using `delimiter` to separate the fields.
+pub fn write_csv_table<H: AsRef<[u8]>>(
+    writer: &mut impl Write,
+    delimiter: u8,
+    header: impl IntoIterator<Item = H>,
+    records: impl IntoIterator<Item = Vec<String>>,
+) -> eyre::Result<()> {
+    let mut csv_writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    csv_writer.write_record(header)?;
+    for record in records {
+        csv_writer.write_record(record)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+/// Return the CSV columns identifying the author of each row.
+/// The email column is only there when emails are shown.
+pub fn get_author_csv_header(show_emails: bool) -> Vec<String> {
+    let mut header = vec!["author_name".to_string()];
+    if show_emails {
+        header.push("author_email".to_string());
+    }
+    header
+}
+
+/// Return the fields matching [`get_author_csv_header`] for the author with `name` and `email`.
+pub fn get_author_csv_fields(name: &str, email: Option<&str>, show_emails: bool) -> Vec<String> {
+    let mut fields = vec![name.to_string()];
+    if show_emails {
+        fields.push(email.unwrap_or_default().to_string());
+    }
+    fields
+}
+
+/// Write `title` (if any) to `writer` as the heading of a markdown report.
+pub fn write_markdown_title(writer: &mut impl Write, title: Option<&str>) -> eyre::Result<()> {
+    if let Some(title) = title {
+        writeln!(writer, "## {title}")?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write `header` and `rows` to `writer` as a GitHub flavored markdown table.
+/// The columns listed in `text_columns` are aligned to the left,
+/// while all of the others (usually numbers) are aligned to the right.
+pub fn write_markdown_table(
+    writer: &mut impl Write,
+    header: &[&str],
+    rows: &[Vec<String>],
+    text_columns: &[usize],
+) -> eyre::Result<()> {
+    let alignments: Vec<&str> = (0..header.len())
+        .map(|i| match text_columns.contains(&i) {
+            true => "---",
+            false => "---:",
+        })
+        .collect();
+    writeln!(writer, "| {} |", header.join(" | "))?;
+    writeln!(writer, "| {} |", alignments.join(" | "))?;
+    for row in rows {
+        // A raw pipe would be interpreted as the start of a new cell
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        writeln!(writer, "| {} |", cells.join(" | "))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_markdown_table_aligns_the_numbers_and_escapes_the_pipes() {
+        let rows = vec![
+            vec![
+                "Jane | Doe".to_string(),
+                "2024-01-02".to_string(),
+                "3".to_string(),
+            ],
+            vec![
+                "John".to_string(),
+                "2024-02-03".to_string(),
+                "10".to_string(),
+            ],
+        ];
+        let mut output = Vec::new();
+        write_markdown_table(&mut output, &["Author", "Date", "Commits"], &rows, &[0, 1]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "| Author | Date | Commits |\n\
+             | --- | --- | ---: |\n\
+             | Jane \\| Doe | 2024-01-02 | 3 |\n\
+             | John | 2024-02-03 | 10 |\n"
+        );
+    }
+}
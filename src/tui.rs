@@ -0,0 +1,685 @@
+//! The `--interactive` mode: a table of the authors that can be scrolled, sorted and filtered
+//! without going through the history again, along with the details of the selected author.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use color_eyre::eyre;
+use console::{measure_text_width, pad_str, truncate_str, Alignment, Key, Term};
+use owo_colors::Style;
+
+use whos_done_that::{
+    aggregate_by_author, aggregate_by_path, compute_percentages, is_author_requested, AuthorData,
+    Commit,
+};
+
+use crate::activity::{count_commits_per_period, get_period_name, PeriodCounts};
+use crate::authors::{compute_scores, count_coauthored_commits, sort_authors};
+use crate::output::{get_sparkline, Palette, MAX_TABLE_AUTHOR_WIDTH};
+use crate::{Cli, SortBy, TimelinePeriod};
+
+/// How many of the files changed by the selected author are listed in the detail pane.
+const NUM_TOP_FILES: usize = 5;
+
+/// Lines taken by the detail pane: a separator, the name, two lines of stats, the activity,
+/// the heading of the top files and the files themselves.
+const DETAIL_PANE_HEIGHT: usize = 6 + NUM_TOP_FILES;
+
+/// Lines taken by the title, the header of the table and the status line.
+const CHROME_HEIGHT: usize = 3;
+
+/// Switch to the alternate screen, so that the shell is left as it was, and hide the cursor.
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+
+/// Undo `ENTER_SCREEN`.
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+
+/// Whether the terminal is on the alternate screen, so that it's only left once.
+static IS_ON_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// The commits of each author in each month, quarter or year.
+struct Activity<'a> {
+    period: TimelinePeriod,
+    /// e.g. `2024-03`, `2024-Q1` or `2024`, from the oldest to the newest
+    period_names: Vec<String>,
+    /// By email, in the same order as the periods
+    counts: HashMap<&'a str, Vec<usize>>,
+}
+
+/// The state of the interactive mode, which is computed once and then only re-sorted
+/// and filtered, so that moving around is instant.
+struct App<'a> {
+    /// The repo and the revisions that were analyzed
+    title: String,
+    /// Sorted according to `sort_column` and `reverse`. The emails are always kept,
+    /// since they identify the authors
+    authors_data: Vec<AuthorData>,
+    show_emails: bool,
+    /// The paths that each author (by email) changed the most lines of, with those lines
+    top_files: HashMap<String, Vec<(String, usize)>>,
+    /// From the finest to the coarsest period, see `get_activity_line`
+    activities: Vec<Activity<'a>>,
+    /// Each column of the table, along with the order it sorts the authors by
+    columns: Vec<(&'static str, SortBy)>,
+    sort_column: usize,
+    reverse: bool,
+    /// Only the authors whose name or email contains it are listed, like with `--author`
+    filter: String,
+    /// Whether the keys are typed into `filter`, after pressing '/'
+    is_filtering: bool,
+    /// Indices in `authors_data` of the listed authors
+    visible: Vec<usize>,
+    /// Index in `visible` of the selected author
+    selected: usize,
+    /// Index in `visible` of the first row of the table
+    scroll: usize,
+    /// Number of rows of the table, as of the last rendering
+    page_size: usize,
+    palette: Palette,
+}
+
+impl<'a> App<'a> {
+    /// Compute the stats of each author from `commits`, with the filters requested in `cli`.
+    fn new(cli: &Cli, title: String, commits: &'a [Commit], palette: Palette) -> Self {
+        let mut authors_data = aggregate_by_author(commits);
+        if let Some(half_life) = &cli.half_life {
+            compute_scores(&mut authors_data, commits, half_life);
+        }
+        if cli.credit_coauthors {
+            count_coauthored_commits(&mut authors_data, commits);
+        }
+        // Like in the reports, the shares are relative to the whole repo
+        compute_percentages(&mut authors_data);
+        authors_data.retain(|a| {
+            let email = a.author_email.as_deref().unwrap_or_default();
+            a.num_commits > 0
+                && is_author_requested(&a.author_name, email, &cli.author)
+                && cli.min_commits.is_none_or(|n| a.num_commits >= n)
+                && cli
+                    .min_lines
+                    .is_none_or(|n| a.code_edits.changed_lines() >= n)
+        });
+
+        let mut top_files: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+        for file_data in aggregate_by_path(commits, usize::MAX, str::to_string) {
+            for author in file_data.authors {
+                let changed_lines = author.code_edits.changed_lines();
+                if changed_lines > 0 {
+                    let email = author.author_email.unwrap_or_default();
+                    let files = top_files.entry(email).or_default();
+                    files.push((file_data.path.clone(), changed_lines));
+                }
+            }
+        }
+        for files in top_files.values_mut() {
+            files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            files.truncate(NUM_TOP_FILES);
+        }
+
+        let activities = [
+            TimelinePeriod::Monthly,
+            TimelinePeriod::Quarterly,
+            TimelinePeriod::Yearly,
+        ]
+        .into_iter()
+        .map(|period| {
+            let PeriodCounts { periods, counts } = count_commits_per_period(commits, period);
+            Activity {
+                period,
+                period_names: periods
+                    .iter()
+                    .map(|p| get_period_name(*p, period))
+                    .collect(),
+                counts,
+            }
+        })
+        .collect();
+
+        let mut columns = vec![
+            ("Author", SortBy::Name),
+            ("Commits", SortBy::Commits),
+            ("Additions", SortBy::Additions),
+            ("Removals", SortBy::Removals),
+            ("Net", SortBy::Net),
+            ("Active days", SortBy::ActiveDays),
+            ("Last commit", SortBy::Recency),
+        ];
+        if cli.half_life.is_some() {
+            columns.push(("Score", SortBy::Score));
+        }
+        // Sorting by a column that isn't shown picks the closest one that is
+        let sort_column = columns
+            .iter()
+            .position(|(_, sort_by)| *sort_by == cli.sort_by)
+            .unwrap_or(1);
+
+        let mut app = Self {
+            title,
+            authors_data,
+            show_emails: cli.show_emails,
+            top_files,
+            activities,
+            columns,
+            sort_column,
+            reverse: cli.reverse,
+            filter: String::new(),
+            is_filtering: false,
+            visible: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            page_size: 1,
+            palette,
+        };
+        app.sort_and_filter();
+        app
+    }
+
+    /// Return the selected author, if any is listed.
+    fn selected_author(&self) -> Option<&AuthorData> {
+        let index = self.visible.get(self.selected)?;
+        self.authors_data.get(*index)
+    }
+
+    /// Sort the authors and list the ones matching the filter, keeping the same author
+    /// selected if it's still listed.
+    fn sort_and_filter(&mut self) {
+        let selected_email = self.selected_author().and_then(|a| a.author_email.clone());
+
+        sort_authors(&mut self.authors_data, self.columns[self.sort_column].1);
+        if self.reverse {
+            self.authors_data.reverse();
+        }
+        let filter = std::slice::from_ref(&self.filter);
+        self.visible = (0..self.authors_data.len())
+            .filter(|&i| {
+                let a = &self.authors_data[i];
+                let email = a.author_email.as_deref().unwrap_or_default();
+                self.filter.is_empty() || is_author_requested(&a.author_name, email, filter)
+            })
+            .collect();
+
+        self.selected = self
+            .visible
+            .iter()
+            .position(|&i| self.authors_data[i].author_email == selected_email)
+            .unwrap_or(0);
+    }
+
+    /// Move the selection by `offset` rows, stopping at the first and last authors.
+    fn move_selection(&mut self, offset: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(offset).min(last);
+    }
+
+    /// Sort by the column at `index`, or reverse the order if it was already sorted by it.
+    fn sort_by_column(&mut self, index: usize) {
+        if index == self.sort_column {
+            self.reverse = !self.reverse;
+        } else {
+            self.sort_column = index;
+            self.reverse = false;
+        }
+        self.sort_and_filter();
+    }
+
+    /// Apply `key`. Return false once the interactive mode should be quit.
+    fn handle_key(&mut self, key: Key) -> bool {
+        let page_size = self.page_size as isize;
+        match key {
+            Key::CtrlC => return false,
+            Key::ArrowUp => self.move_selection(-1),
+            Key::ArrowDown => self.move_selection(1),
+            Key::PageUp => self.move_selection(-page_size),
+            Key::PageDown => self.move_selection(page_size),
+            Key::Home => self.selected = 0,
+            Key::End => self.move_selection(isize::MAX),
+            // While filtering, the other keys are typed into the filter
+            Key::Enter if self.is_filtering => self.is_filtering = false,
+            Key::Escape if self.is_filtering || !self.filter.is_empty() => {
+                self.is_filtering = false;
+                self.filter.clear();
+                self.sort_and_filter();
+            }
+            Key::Backspace if self.is_filtering => {
+                self.filter.pop();
+                self.sort_and_filter();
+            }
+            Key::Char(c) if self.is_filtering && !c.is_control() => {
+                self.filter.push(c);
+                self.sort_and_filter();
+            }
+            Key::Char('q') | Key::Escape => return false,
+            Key::Char('/') => self.is_filtering = true,
+            Key::Char('k') => self.move_selection(-1),
+            Key::Char('j') => self.move_selection(1),
+            Key::Char('g') => self.selected = 0,
+            Key::Char('G') => self.move_selection(isize::MAX),
+            Key::Char('r') => {
+                self.reverse = !self.reverse;
+                self.sort_and_filter();
+            }
+            Key::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < self.columns.len() {
+                    self.sort_by_column(index);
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    /// Return the name of `author_data` as listed, with the email only if it was requested.
+    fn display_name(&self, author_data: &AuthorData) -> String {
+        match self.show_emails {
+            true => author_data.display_name(),
+            false => author_data.author_name.clone(),
+        }
+    }
+
+    /// Return the cells of the row of `author_data`, in the same order as `columns`.
+    fn get_cells(&self, author_data: &AuthorData) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|(_, sort_by)| match sort_by {
+                SortBy::Name => {
+                    let name = self.display_name(author_data);
+                    truncate_str(&name, MAX_TABLE_AUTHOR_WIDTH, "…").into_owned()
+                }
+                SortBy::Commits => author_data.num_commits.to_string(),
+                SortBy::Additions => author_data.code_edits.additions.to_string(),
+                SortBy::Removals => author_data.code_edits.removals.to_string(),
+                SortBy::Net => author_data.net_lines().to_string(),
+                SortBy::ActiveDays => author_data.active_days.to_string(),
+                SortBy::Recency => author_data
+                    .last_commit_date()
+                    .unwrap_or_default()
+                    .to_string(),
+                SortBy::Score => format!("{:.2}", author_data.score.unwrap_or(0.0)),
+            })
+            .collect()
+    }
+
+    /// Return the lines of the screen, fitting in `width` columns and `height` rows.
+    fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        // Small terminals only get the table
+        let show_details = height >= CHROME_HEIGHT + DETAIL_PANE_HEIGHT + 3;
+        let table_height = match show_details {
+            true => height - CHROME_HEIGHT - DETAIL_PANE_HEIGHT,
+            false => height.saturating_sub(CHROME_HEIGHT).max(1),
+        };
+        self.page_size = table_height;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + table_height {
+            self.scroll = self.selected + 1 - table_height;
+        }
+
+        // The widths are computed on all of the authors, so that filtering doesn't move the columns
+        let rows: Vec<Vec<String>> = self
+            .authors_data
+            .iter()
+            .map(|a| self.get_cells(a))
+            .collect();
+        let header: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, (title, sort_by))| {
+                // Names are sorted alphabetically, while everything else starts from the highest
+                let is_descending = (*sort_by != SortBy::Name) != self.reverse;
+                match i == self.sort_column {
+                    true if is_descending => format!("{} {title} ▼", i + 1),
+                    true => format!("{} {title} ▲", i + 1),
+                    false => format!("{} {title}", i + 1),
+                }
+            })
+            .collect();
+        let mut widths: Vec<usize> = header.iter().map(|h| measure_text_width(h)).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(measure_text_width(cell));
+            }
+        }
+        let format_row = |cells: &[String]| -> String {
+            cells
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, &width))| {
+                    let alignment = if i == 0 {
+                        Alignment::Left
+                    } else {
+                        Alignment::Right
+                    };
+                    pad_str(cell, width, alignment, None).into_owned()
+                })
+                .collect::<Vec<String>>()
+                .join("  ")
+        };
+
+        let fit = |line: &str| truncate_str(line, width, "…").into_owned();
+        let mut lines = vec![
+            self.palette.paint(&fit(&self.title), Style::new().bold()),
+            self.palette.paint(
+                &fit(&format!("  {}", format_row(&header))),
+                Style::new().bold(),
+            ),
+        ];
+
+        let visible_rows = self.visible.iter().skip(self.scroll).take(table_height);
+        for (row_index, &author_index) in visible_rows.enumerate() {
+            if self.scroll + row_index == self.selected {
+                // Marked even without colors, so that the selection is always visible
+                let line = fit(&format!("> {}", format_row(&rows[author_index])));
+                let line = pad_str(&line, width, Alignment::Left, None).into_owned();
+                lines.push(self.palette.paint(&line, Style::new().reversed()));
+            } else {
+                lines.push(fit(&format!("  {}", format_row(&rows[author_index]))));
+            }
+        }
+        if self.visible.is_empty() {
+            lines.push(fit(&format!("  No author matches '{}'", self.filter)));
+        }
+        lines.resize(CHROME_HEIGHT - 1 + table_height, String::new());
+
+        if show_details {
+            let mut detail_lines = self.get_detail_lines(width);
+            detail_lines.resize(DETAIL_PANE_HEIGHT, String::new());
+            lines.extend(detail_lines.iter().map(|l| fit(l)));
+        }
+
+        let status = match self.is_filtering {
+            true => format!("/{}█  (Enter to keep, Esc to clear)", self.filter),
+            false => {
+                let mut status = format!(
+                    "{}/{} authors  ↑↓ move  1-{} sort  r reverse  / filter  q quit",
+                    self.visible.len(),
+                    self.authors_data.len(),
+                    self.columns.len()
+                );
+                if !self.filter.is_empty() {
+                    status.push_str(&format!("  (filtered by '{}', Esc to clear)", self.filter));
+                }
+                status
+            }
+        };
+        lines.push(self.palette.paint(&fit(&status), Style::new().dimmed()));
+        lines.truncate(height);
+
+        lines
+    }
+
+    /// Return the lines of the detail pane, about the selected author.
+    fn get_detail_lines(&self, width: usize) -> Vec<String> {
+        let mut lines = vec!["─".repeat(width)];
+        let Some(author_data) = self.selected_author() else {
+            return lines;
+        };
+
+        let name = self.display_name(author_data);
+        lines.push(self.palette.paint(&name, Style::new().bold()));
+        lines.push(format!(
+            "{} commits ({:.1}% of commits), {} ({:.1}% of additions)",
+            author_data.num_commits,
+            author_data.percent_commits,
+            author_data.code_edits,
+            author_data.percent_additions
+        ));
+        if let (Some(first), Some(last)) = (
+            author_data.first_commit_date(),
+            author_data.last_commit_date(),
+        ) {
+            let days = match author_data.active_days {
+                1 => "1 day".to_string(),
+                n => format!("{n} days"),
+            };
+            lines.push(format!(
+                "Active on {days} from {first} to {last}, {:.1} lines changed per commit on average",
+                author_data.avg_lines_per_commit
+            ));
+        }
+
+        let email = author_data.author_email.as_deref().unwrap_or_default();
+        lines.extend(self.get_activity_line(email, width));
+
+        lines.push("Top files:".to_string());
+        let files = self.top_files.get(email).map_or(&[][..], Vec::as_slice);
+        let lines_width = files
+            .iter()
+            .map(|(_, n)| n.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for (path, changed_lines) in files {
+            lines.push(format!("  {changed_lines:>lines_width$} lines  {path}"));
+        }
+
+        lines
+    }
+
+    /// Return the sparkline of the commits of the author with `email`, using the finest
+    /// period that fits in `width`. The oldest periods are cut when even the years don't fit.
+    fn get_activity_line(&self, email: &str, width: usize) -> Option<String> {
+        let format_label = |activity: &Activity, first: &str, last: &str| {
+            let unit = match activity.period {
+                TimelinePeriod::Monthly => "month",
+                TimelinePeriod::Quarterly => "quarter",
+                TimelinePeriod::Yearly => "year",
+            };
+            format!("Commits per {unit} from {first} to {last}: ")
+        };
+
+        let fits = |activity: &&Activity| {
+            let first = activity.period_names.first().map_or("", String::as_str);
+            let last = activity.period_names.last().map_or("", String::as_str);
+            let label_width = measure_text_width(&format_label(activity, first, last));
+            label_width + activity.period_names.len() <= width
+        };
+        let activity = self
+            .activities
+            .iter()
+            .find(fits)
+            .or(self.activities.last())?;
+        let counts = activity.counts.get(email)?;
+
+        let last = activity.period_names.last()?;
+        let label_width = measure_text_width(&format_label(activity, last, last));
+        let num_periods = counts.len().min(width.saturating_sub(label_width)).max(1);
+        let first_index = counts.len() - num_periods;
+        let label = format_label(activity, &activity.period_names[first_index], last);
+
+        Some(format!("{label}{}", get_sparkline(&counts[first_index..])))
+    }
+}
+
+/// Keeps the terminal on the alternate screen until dropped, including when panicking.
+struct Screen;
+
+impl Screen {
+    fn enter(term: &Term) -> eyre::Result<Self> {
+        // The panic hook runs before unwinding, so the message would be printed
+        // on the alternate screen and lost as soon as the screen is left
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            leave_screen();
+            default_hook(info);
+        }));
+
+        term.write_str(ENTER_SCREEN)?;
+        IS_ON_SCREEN.store(true, Ordering::SeqCst);
+        Ok(Self)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        leave_screen();
+    }
+}
+
+/// Leave the alternate screen, unless it was already left.
+fn leave_screen() {
+    if IS_ON_SCREEN.swap(false, Ordering::SeqCst) {
+        // Nothing can be done about a terminal that can't be written to anymore
+        let _ = Term::stdout().write_str(LEAVE_SCREEN);
+    }
+}
+
+/// Draw `lines` from the top left corner of `term`, in a single write so that it doesn't flicker.
+fn draw(term: &Term, lines: &[String]) -> std::io::Result<()> {
+    let mut frame = String::from("\x1b[H");
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            frame.push_str("\r\n");
+        }
+        frame.push_str(line);
+        // Clear what's left of the previous frame
+        frame.push_str("\x1b[K");
+    }
+    frame.push_str("\x1b[J");
+
+    term.write_str(&frame)
+}
+
+/// Explore the authors of `commits` in the terminal, until 'q' is pressed.
+/// `title` names what was analyzed.
+pub fn run(cli: &Cli, title: String, commits: &[Commit], palette: Palette) -> eyre::Result<()> {
+    let mut app = App::new(cli, title, commits, palette);
+
+    let term = Term::stdout();
+    let _screen = Screen::enter(&term)?;
+    loop {
+        let (height, width) = term.size();
+        let lines = app.render(usize::from(width), usize::from(height));
+        draw(&term, &lines)?;
+
+        // Unlike `read_key`, Ctrl-C is returned as a key, so that the screen is left properly
+        if !app.handle_key(term.read_key_raw()?) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use whos_done_that::FileChange;
+
+    use super::*;
+
+    /// Return a commit of `name <email>` made on `date`, adding `additions` lines to `path`.
+    fn make_commit(name: &str, date: &str, path: &str, additions: usize) -> Commit {
+        Commit {
+            author_name: name.to_string(),
+            author_email: format!("{}@example.com", name.to_lowercase()),
+            timestamp: 0,
+            date: date.to_string(),
+            datetime: format!("{date}T12:00:00+00:00"),
+            weekday: 0,
+            hour: 12,
+            co_authors: Vec::new(),
+            is_coauthored: false,
+            file_changes: vec![FileChange {
+                path: path.to_string(),
+                renamed_from: None,
+                additions,
+                removals: 0,
+                is_binary: false,
+            }],
+        }
+    }
+
+    fn get_commits() -> Vec<Commit> {
+        vec![
+            make_commit("Jane", "2024-03-01", "src/lib.rs", 1),
+            make_commit("Jane", "2024-02-01", "src/main.rs", 2),
+            make_commit("John", "2024-02-01", "src/lib.rs", 30),
+            make_commit("Joanna", "2024-01-01", "README.md", 4),
+        ]
+    }
+
+    fn get_listed_names(app: &App) -> Vec<String> {
+        app.visible
+            .iter()
+            .map(|&i| app.authors_data[i].author_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn sorting_by_the_same_column_twice_reverses_the_order() {
+        let cli = Cli::parse_from(["whos-done-that", "--interactive"]);
+        let commits = get_commits();
+        let mut app = App::new(
+            &cli,
+            "repo".to_string(),
+            &commits,
+            Palette { enabled: false },
+        );
+        assert_eq!(get_listed_names(&app), ["Jane", "Joanna", "John"]);
+
+        // The selection follows the author
+        app.handle_key(Key::ArrowDown);
+        app.handle_key(Key::Char('3'));
+        assert_eq!(get_listed_names(&app), ["John", "Joanna", "Jane"]);
+        assert_eq!(app.selected_author().unwrap().author_name, "Joanna");
+
+        app.handle_key(Key::Char('3'));
+        assert_eq!(get_listed_names(&app), ["Jane", "Joanna", "John"]);
+    }
+
+    #[test]
+    fn filtering_lists_the_matching_authors_until_cleared() {
+        let cli = Cli::parse_from(["whos-done-that", "--interactive"]);
+        let commits = get_commits();
+        let mut app = App::new(
+            &cli,
+            "repo".to_string(),
+            &commits,
+            Palette { enabled: false },
+        );
+
+        for key in [Key::Char('/'), Key::Char('J'), Key::Char('o'), Key::Enter] {
+            app.handle_key(key);
+        }
+        assert_eq!(get_listed_names(&app), ["Joanna", "John"]);
+        // Once the filter is kept, the keys are commands again
+        assert!(!app.handle_key(Key::Char('q')));
+
+        app.handle_key(Key::Escape);
+        assert_eq!(get_listed_names(&app), ["Jane", "Joanna", "John"]);
+    }
+
+    #[test]
+    fn render_fits_in_the_terminal_and_details_the_selection() {
+        let cli = Cli::parse_from(["whos-done-that", "--interactive"]);
+        let commits = get_commits();
+        let mut app = App::new(
+            &cli,
+            "repo".to_string(),
+            &commits,
+            Palette { enabled: false },
+        );
+
+        let lines = app.render(60, 24);
+        assert_eq!(lines.len(), 24);
+        assert!(
+            lines.iter().all(|l| measure_text_width(l) <= 60),
+            "{lines:#?}"
+        );
+        assert!(lines[2].starts_with("> Jane"), "{lines:#?}");
+        assert!(
+            lines.contains(&"Commits per month from 2024-01 to 2024-03:  ██".to_string()),
+            "{lines:#?}"
+        );
+        assert!(
+            lines.contains(&"  2 lines  src/main.rs".to_string()),
+            "{lines:#?}"
+        );
+
+        // Without room for the details, only the table is left
+        let lines = app.render(60, 8);
+        assert_eq!(lines.len(), 8);
+        assert!(!lines.iter().any(|l| l.starts_with("Top files")));
+    }
+}
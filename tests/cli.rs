@@ -0,0 +1,825 @@
+//! Tests of the command line against real git repositories.
+
+mod common;
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::TestRepo;
+
+const JANE: &str = "Jane Doe <jane@example.com>";
+const JOHN: &str = "John Smith <john@example.com>";
+
+/// Run the binary inside `repo` with `args`, isolated from the config of the user.
+fn run(repo: &TestRepo, args: &[&str]) -> Output {
+    run_in(&repo.path, args)
+}
+
+/// Run the binary inside `dir` with `args`, isolated from the config of the user.
+fn run_in(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_whos-done-that"))
+        .current_dir(dir)
+        .args(args)
+        .env(
+            "GIT_CONFIG_GLOBAL",
+            if cfg!(windows) { "NUL" } else { "/dev/null" },
+        )
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .unwrap()
+}
+
+/// Run the binary inside `repo` with `args` and return its stdout, panicking if it fails.
+fn run_ok(repo: &TestRepo, args: &[&str]) -> String {
+    let output = run(repo, args);
+    assert!(
+        output.status.success(),
+        "whos-done-that {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Return the authors reported as JSON by running the binary inside `repo` with `args`.
+fn run_json(repo: &TestRepo, args: &[&str]) -> Vec<serde_json::Value> {
+    let mut args = args.to_vec();
+    args.extend(["--format", "json"]);
+    serde_json::from_str(&run_ok(repo, &args)).unwrap()
+}
+
+/// Return the value of `key` for each author of `authors`.
+fn get_field<'a>(authors: &'a [serde_json::Value], key: &str) -> Vec<&'a serde_json::Value> {
+    authors.iter().map(|a| &a[key]).collect()
+}
+
+#[test]
+fn mailmap_merges_the_identities_of_an_author() {
+    let repo = TestRepo::new("cli-mailmap");
+    repo.write("a.txt", "a\n");
+    repo.commit("Alice <alice@old.example.com>", "Add a");
+    repo.write("b.txt", "b\nb\n");
+    repo.commit("alice <alice@new.example.com>", "Add b");
+    repo.write(
+        ".mailmap",
+        "Alice Liddell <alice@new.example.com> <alice@old.example.com>\n\
+         Alice Liddell <alice@new.example.com>\n",
+    );
+    repo.commit(JOHN, "Add the mailmap");
+
+    let authors = run_json(&repo, &[]);
+
+    assert_eq!(
+        get_field(&authors, "author_name"),
+        ["Alice Liddell", "John Smith"]
+    );
+    assert_eq!(get_field(&authors, "num_commits"), [2, 1]);
+    assert_eq!(get_field(&authors, "additions"), [3, 2]);
+}
+
+#[test]
+fn commits_reachable_from_several_branches_are_counted_once() {
+    let repo = TestRepo::new("cli-several-branches");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.git(["branch", "copy"]);
+    repo.git(["checkout", "-q", "-b", "feature"]);
+    repo.write("b.txt", "b\nb\n");
+    repo.commit(JOHN, "Add b");
+    repo.git(["branch", "feature-copy"]);
+    repo.git(["checkout", "-q", "main"]);
+
+    // The commits and the lines always cover the same set of commits
+    let authors = run_json(&repo, &[]);
+    assert_eq!(get_field(&authors, "author_name"), ["Jane Doe"]);
+    assert_eq!(get_field(&authors, "num_commits"), [1]);
+    assert_eq!(get_field(&authors, "additions"), [1]);
+
+    let authors = run_json(&repo, &["--branch", "feature"]);
+    assert_eq!(
+        get_field(&authors, "author_name"),
+        ["Jane Doe", "John Smith"]
+    );
+    assert_eq!(get_field(&authors, "num_commits"), [1, 1]);
+    assert_eq!(get_field(&authors, "additions"), [1, 2]);
+}
+
+#[test]
+fn min_commits_and_min_lines_filter_out_the_low_activity_authors() {
+    let repo = TestRepo::new("cli-min-commits");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.write("a.txt", "a\nb\n");
+    repo.commit(JANE, "Update a");
+    repo.write("b.txt", "1\n2\n3\n4\n5\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("c.txt", "c\n");
+    repo.commit("Ann <ann@example.com>", "Fix a typo");
+
+    let output = run(&repo, &["--min-commits", "2", "--format", "csv"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(names, ["Jane Doe"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Filtered 2 authors below 2 commits"),
+        "{stderr}"
+    );
+
+    let authors = run_json(&repo, &["--min-lines", "2"]);
+    assert_eq!(
+        get_field(&authors, "author_name"),
+        ["Jane Doe", "John Smith"]
+    );
+}
+
+#[test]
+fn analyzes_repos_with_spaces_and_parentheses_in_their_path() {
+    let repo = TestRepo::new("my repo (copy)");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    let parent_dir = repo.path.parent().unwrap();
+
+    let target_dir = repo.path.to_str().unwrap();
+    let output = run_in(parent_dir, &["--target-dir", target_dir, "--format", "csv"]);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().nth(1).unwrap().starts_with("Jane Doe,1,1,0"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn analyzes_the_master_branch_by_default() {
+    let repo = TestRepo::with_branch("cli-master", "master");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    let output = run(&repo, &["--format", "csv"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("'master'"), "{stderr}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().nth(1).unwrap().starts_with("Jane Doe,1,"),
+        "{stdout}"
+    );
+
+    // The default branch of the remote wins over the current branch
+    let clone = TestRepo::new("cli-master-clone");
+    clone.git(["remote", "add", "origin", repo.path.to_str().unwrap()]);
+    clone.git(["fetch", "-q", "origin"]);
+    clone.git(["remote", "set-head", "origin", "master"]);
+    clone.git(["checkout", "-q", "-b", "feature", "origin/master"]);
+    clone.write("b.txt", "b\n");
+    clone.commit(JOHN, "Add b");
+
+    let authors = run_json(&clone, &[]);
+    assert_eq!(get_field(&authors, "author_name"), ["Jane Doe"]);
+}
+
+/// Put a wrapper of git first in the PATH, which runs `script` before running git itself.
+/// Return the new PATH.
+#[cfg(unix)]
+fn install_git_wrapper(repo: &TestRepo, script: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let output = Command::new("sh")
+        .args(["-c", "command -v git"])
+        .output()
+        .unwrap();
+    let real_git = String::from_utf8(output.stdout).unwrap();
+    let wrapper_dir = repo.path.join(".git/wrapper");
+    let wrapper = wrapper_dir.join("git");
+    std::fs::create_dir_all(&wrapper_dir).unwrap();
+    std::fs::write(
+        &wrapper,
+        format!("#!/bin/sh\n{script}\nexec '{}' \"$@\"\n", real_git.trim()),
+    )
+    .unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    format!(
+        "{}:{}",
+        wrapper_dir.display(),
+        std::env::var("PATH").unwrap()
+    )
+}
+
+/// git must not block when it prints a lot to stderr while the history is being read.
+#[cfg(unix)]
+#[test]
+fn lots_of_warnings_from_git_log_dont_block() {
+    let repo = TestRepo::new("cli-stderr");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    // Way more than what the pipe can buffer
+    let path = install_git_wrapper(
+        &repo,
+        "if [ \"$1\" = log ]; then head -c 1000000 /dev/zero | tr '\\000' w >&2; fi",
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_whos-done-that"))
+        .current_dir(&repo.path)
+        .args(["--format", "csv"])
+        .env("PATH", path)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().nth(1).unwrap().starts_with("Jane Doe,1,"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn messages_arent_colored_when_not_written_to_a_terminal() {
+    let repo = TestRepo::new("cli-message-colors");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_whos-done-that"))
+        .current_dir(&repo.path)
+        .env(
+            "GIT_CONFIG_GLOBAL",
+            if cfg!(windows) { "NUL" } else { "/dev/null" },
+        )
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("INFO"), "{stderr}");
+    assert!(!stderr.contains('\x1b'), "{stderr:?}");
+}
+
+#[test]
+fn bus_factor_reports_the_threshold_as_a_round_percentage() {
+    let repo = TestRepo::new("cli-bus-factor");
+    repo.write("src/a.txt", "1\n2\n3\n4\n5\n6\n");
+    repo.commit(JANE, "Add a");
+    repo.write("src/b.txt", "1\n2\n3\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("docs/c.txt", "1\n");
+    repo.commit("Ann <ann@example.com>", "Add c");
+
+    let stdout = run_ok(
+        &repo,
+        &["bus-factor", "--metric", "additions", "--threshold", "0.57"],
+    );
+    assert_eq!(
+        stdout.lines().next(),
+        Some("Bus factor of the repo, covering 57% of the additions: 1, at risk! (Jane Doe)")
+    );
+
+    let output = run(&repo, &["bus-factor", "--threshold", "1.5"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'1.5' is not a valid threshold"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn bus_factor_covers_half_of_the_commits_by_default() {
+    let repo = TestRepo::new("cli-bus-factor-default");
+    for i in 0..3 {
+        repo.write("a.txt", &format!("{i}\n"));
+        repo.commit(JANE, "Update a");
+    }
+    repo.write("b.txt", "1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("c.txt", "c\n");
+    repo.commit("Ann <ann@example.com>", "Add c");
+
+    let stdout = run_ok(&repo, &["bus-factor"]);
+    assert_eq!(
+        stdout.lines().next(),
+        Some("Bus factor of the repo, covering 50% of the commits: 1, at risk! (Jane Doe)")
+    );
+}
+
+#[test]
+fn special_characters_are_passed_to_git_as_they_are() {
+    // Would be expanded or break the quoting if git was run through a shell
+    let repo = TestRepo::new("shell $HOME `ls` 'quoted' \"twice\"");
+    repo.write("a.txt", "a\n");
+    repo.commit("Conan \"Coco\" O'Brien <conan@example.com>", "Add a");
+    repo.write("b.txt", "b\n");
+    repo.commit("Zoë 日本 $(whoami) <zoe@example.com>", "Add b");
+    repo.write("c.txt", "c\n");
+    repo.commit("Line\nBreak <line@example.com>", "Add c");
+    let parent_dir = repo.path.parent().unwrap();
+
+    let target_dir = repo.path.to_str().unwrap();
+    let output = run_in(
+        parent_dir,
+        &["--target-dir", target_dir, "--format", "json"],
+    );
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let authors: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let mut names = get_field(&authors, "author_name");
+    names.sort_by_key(|name| name.as_str());
+    assert_eq!(
+        names,
+        [
+            "Conan \"Coco\" O'Brien",
+            // git drops the line breaks of the names
+            "LineBreak",
+            "Zoë 日本 $(whoami)",
+        ]
+    );
+}
+
+#[test]
+fn only_the_requested_branch_is_analyzed() {
+    let repo = TestRepo::new("cli-branches");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.git(["checkout", "-q", "-b", "release/2.x"]);
+    repo.write("b.txt", "b\n");
+    repo.commit(JOHN, "Backport b");
+    repo.git(["checkout", "-q", "main"]);
+    repo.write("c.txt", "c\n");
+    repo.commit(JANE, "Add c");
+    repo.write("d.txt", "d\n");
+    repo.commit(JANE, "Add d");
+
+    let count_commits = |args: &[&str]| -> Vec<(String, u64)> {
+        run_json(&repo, args)
+            .iter()
+            .map(|a| {
+                let name = a["author_name"].as_str().unwrap().to_string();
+                (name, a["num_commits"].as_u64().unwrap())
+            })
+            .collect()
+    };
+
+    assert_eq!(count_commits(&[]), [("Jane Doe".to_string(), 3)]);
+    assert_eq!(
+        count_commits(&["--branch", "release/2.x"]),
+        [("Jane Doe".to_string(), 1), ("John Smith".to_string(), 1)]
+    );
+    assert_eq!(
+        count_commits(&["--all-branches"]),
+        [("Jane Doe".to_string(), 3), ("John Smith".to_string(), 1)]
+    );
+}
+
+#[test]
+fn credit_coauthors_credits_each_coauthor_of_a_commit() {
+    let repo = TestRepo::new("cli-coauthors");
+    repo.write("a.txt", "1\n2\n3\n4\n5\n6\n");
+    repo.commit(
+        JANE,
+        "Pair on a\n\n\
+         Co-authored-by: Ann <ann@example.com>\n\
+         Co-authored-by: Bob <bob@example.com>\n",
+    );
+    repo.write("b.txt", "b\n");
+    repo.commit(JANE, "Add b");
+
+    let get_credits = |args: &[&str]| -> Vec<(String, u64, u64)> {
+        let mut args = args.to_vec();
+        args.extend(["--sort-by", "name"]);
+        run_json(&repo, &args)
+            .iter()
+            .map(|a| {
+                let name = a["author_name"].as_str().unwrap().to_string();
+                let commits = a["num_commits"].as_u64().unwrap();
+                (name, commits, a["additions"].as_u64().unwrap())
+            })
+            .collect()
+    };
+
+    assert_eq!(get_credits(&[]), [("Jane Doe".to_string(), 2, 7)]);
+    // The whole commit is credited to each of them
+    assert_eq!(
+        get_credits(&["--credit-coauthors"]),
+        [
+            ("Ann".to_string(), 1, 6),
+            ("Bob".to_string(), 1, 6),
+            ("Jane Doe".to_string(), 2, 7),
+        ]
+    );
+    // Unless the lines are split
+    assert_eq!(
+        get_credits(&["--credit-coauthors", "--split-coauthored-lines"]),
+        [
+            ("Ann".to_string(), 1, 2),
+            ("Bob".to_string(), 1, 2),
+            ("Jane Doe".to_string(), 2, 3),
+        ]
+    );
+
+    let authors = run_json(&repo, &["--credit-coauthors", "--sort-by", "name"]);
+    assert_eq!(
+        get_field(&authors, "coauthored_commits"),
+        [
+            &serde_json::json!(1),
+            &serde_json::json!(1),
+            &serde_json::json!(0)
+        ]
+    );
+}
+
+#[test]
+fn include_merges_credits_the_merge_without_its_lines() {
+    let repo = TestRepo::new("cli-merges");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.git(["checkout", "-q", "-b", "feature"]);
+    repo.write("b.txt", "b\nb\nb\n");
+    repo.commit(JOHN, "Add b");
+    repo.git(["checkout", "-q", "main"]);
+    repo.write("c.txt", "c\n");
+    repo.commit(JANE, "Add c");
+    repo.merge("feature", "Ann <ann@example.com>");
+
+    let get_stats = |args: &[&str]| -> Vec<(String, u64, u64)> {
+        let mut args = args.to_vec();
+        args.extend(["--sort-by", "name"]);
+        run_json(&repo, &args)
+            .iter()
+            .map(|a| {
+                let name = a["author_name"].as_str().unwrap().to_string();
+                let commits = a["num_commits"].as_u64().unwrap();
+                (name, commits, a["additions"].as_u64().unwrap())
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        get_stats(&[]),
+        [
+            ("Jane Doe".to_string(), 2, 2),
+            ("John Smith".to_string(), 1, 3)
+        ]
+    );
+    assert_eq!(
+        get_stats(&["--include-merges"]),
+        [
+            ("Ann".to_string(), 1, 0),
+            ("Jane Doe".to_string(), 2, 2),
+            ("John Smith".to_string(), 1, 3),
+        ]
+    );
+}
+
+#[test]
+fn reports_each_author_as_csv() {
+    let repo = TestRepo::new("cli-csv");
+    repo.write("a.txt", "a\nb\n");
+    repo.commit(JANE, "Add a");
+    repo.write("b.txt", "c\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("b.txt", "d\n");
+    repo.commit(JOHN, "Update b");
+
+    let stdout = run_ok(&repo, &["--format", "csv"]);
+    let mut lines = stdout.lines();
+    let header = lines.next().unwrap();
+    assert!(header.starts_with("author_name,"), "{header}");
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[0].starts_with("John Smith,2,"), "{}", rows[0]);
+    assert!(rows[1].starts_with("Jane Doe,1,"), "{}", rows[1]);
+}
+
+#[test]
+fn files_csv_rounds_the_percentages() {
+    let repo = TestRepo::new("cli-files-csv");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.write("a.txt", "a\nb\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("a.txt", "a\nb\nc\n");
+    repo.commit("Ann <ann@example.com>", "Add c");
+
+    let stdout = run_ok(&repo, &["--group-by", "file", "--format", "csv"]);
+    let rows: Vec<&str> = stdout.lines().skip(1).collect();
+    assert_eq!(rows.len(), 3);
+    for row in rows {
+        assert!(row.ends_with(",1,0,33.3"), "{row}");
+    }
+}
+
+#[test]
+fn fails_outside_of_a_git_repo() {
+    let repo = TestRepo::new("cli-not-a-repo");
+    std::fs::remove_dir_all(repo.path.join(".git")).unwrap();
+
+    let output = run(&repo, &[]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not a git repository"), "{stderr}");
+}
+
+#[test]
+fn reports_the_emails_only_when_requested() {
+    let repo = TestRepo::new("cli-emails");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    let authors = run_json(&repo, &[]);
+    assert_eq!(get_field(&authors, "author_name"), ["Jane Doe"]);
+    assert_eq!(
+        get_field(&authors, "author_email"),
+        [&serde_json::Value::Null]
+    );
+
+    let authors = run_json(&repo, &["--show-emails"]);
+    assert_eq!(get_field(&authors, "author_email"), ["jane@example.com"]);
+}
+
+#[test]
+fn author_names_with_regex_characters_match_literally() {
+    let repo = TestRepo::new("cli-author-regex");
+    repo.write("a.txt", "a\n");
+    repo.commit("A. B. (Corp)+ <ab@example.com>", "Add a");
+    // Both would match 'A. B. (Corp)+' if it was read as a regular expression
+    repo.write("b.txt", "b\n");
+    repo.commit("A. B. Corp <corp@example.com>", "Add b");
+    repo.write("c.txt", "c\n");
+    repo.commit("Ax Bx Corp <x@example.com>", "Add c");
+    repo.write("d.txt", "d\n");
+    repo.commit(JANE, "Add d");
+
+    let authors = run_json(&repo, &["--author", "A. B. (Corp)+", "--show-emails"]);
+
+    assert_eq!(get_field(&authors, "author_name"), ["A. B. (Corp)+"]);
+    assert_eq!(get_field(&authors, "author_email"), ["ab@example.com"]);
+}
+
+#[test]
+fn commits_only_touching_excluded_paths_dont_count() {
+    let repo = TestRepo::new("cli-exclude-generated");
+    repo.write("src/main.rs", "fn main() {}\n");
+    repo.write("Cargo.lock", "version = 3\n");
+    repo.commit(JANE, "Add main");
+    repo.write("Cargo.lock", "version = 4\n[[package]]\n");
+    repo.commit(JOHN, "Bump the dependencies");
+    repo.write("vendor/lib.c", "int x;\n");
+    repo.commit(JOHN, "Vendor lib");
+
+    let get_stats = |args: &[&str]| -> Vec<(String, u64, u64)> {
+        run_json(&repo, args)
+            .iter()
+            .map(|a| {
+                let name = a["author_name"].as_str().unwrap().to_string();
+                let commits = a["num_commits"].as_u64().unwrap();
+                (name, commits, a["additions"].as_u64().unwrap())
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        get_stats(&["--exclude-generated", "--exclude", "vendor/"]),
+        [("Jane Doe".to_string(), 1, 1)]
+    );
+    assert_eq!(
+        get_stats(&["--exclude", "vendor/"]),
+        [
+            ("Jane Doe".to_string(), 1, 2),
+            ("John Smith".to_string(), 1, 2)
+        ]
+    );
+}
+
+#[test]
+fn range_skips_the_commits_before_its_start() {
+    let repo = TestRepo::new("cli-range");
+    repo.write("a.txt", "a\n");
+    repo.commit("Ann <ann@example.com>", "Add a");
+    repo.git(["tag", "v1.0"]);
+    repo.write("b.txt", "b\n");
+    repo.commit(JANE, "Add b");
+    repo.git(["tag", "v2.0"]);
+    repo.write("c.txt", "c\n");
+    repo.commit(JOHN, "Add c");
+
+    let authors = run_json(&repo, &["--range", "v1.0..v2.0"]);
+    assert_eq!(get_field(&authors, "author_name"), ["Jane Doe"]);
+
+    let authors = run_json(&repo, &["--range", "v1.0..", "--sort-by", "name"]);
+    assert_eq!(
+        get_field(&authors, "author_name"),
+        ["Jane Doe", "John Smith"]
+    );
+
+    let output = run(&repo, &["--range", "v1.0..v2.0", "--branch", "main"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+}
+
+#[test]
+fn error_git_not_found() {
+    let repo = TestRepo::new("cli-git-not-found");
+    let empty_dir = repo.path.join(".git/empty-path");
+    std::fs::create_dir_all(&empty_dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_whos-done-that"))
+        .current_dir(&repo.path)
+        .env("PATH", &empty_dir)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to run git"), "{stderr}");
+}
+
+/// Replace the output of `git check-attr` with a truncated answer, by putting a wrapper of git
+/// first in the PATH.
+#[cfg(unix)]
+#[test]
+fn error_invalid_git_output() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let repo = TestRepo::new("cli-invalid-output");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    let real_git = String::from_utf8(
+        Command::new("sh")
+            .args(["-c", "command -v git"])
+            .output()
+            .unwrap()
+            .stdout,
+    )
+    .unwrap();
+    let wrapper_dir = repo.path.join(".git/wrapper");
+    let wrapper = wrapper_dir.join("git");
+    std::fs::create_dir_all(&wrapper_dir).unwrap();
+    std::fs::write(
+        &wrapper,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = check-attr ]; then cat >/dev/null; printf 'a\\000b\\000'; exit 0; fi\nexec '{}' \"$@\"\n",
+            real_git.trim()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!(
+        "{}:{}",
+        wrapper_dir.display(),
+        std::env::var("PATH").unwrap()
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_whos-done-that"))
+        .current_dir(&repo.path)
+        .env("PATH", path)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to parse the output of 'git check-attr"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn totals_count_coauthored_commits_once() {
+    let repo = TestRepo::new("cli-coauthored-totals");
+    repo.write("a.txt", "1\n2\n3\n4\n5\n6\n");
+    repo.commit(
+        JANE,
+        "Pair on a\n\n\
+         Co-authored-by: Ann <ann@example.com>\n\
+         Co-authored-by: Bob <bob@example.com>\n",
+    );
+    repo.write("b.txt", "b\n");
+    repo.commit(JANE, "Add b");
+
+    for args in [
+        &["--credit-coauthors"][..],
+        &["--credit-coauthors", "--split-coauthored-lines"],
+        &[],
+    ] {
+        let mut args = args.to_vec();
+        args.extend(["--totals", "--format", "json"]);
+        let report: serde_json::Value = serde_json::from_str(&run_ok(&repo, &args)).unwrap();
+        let totals = &report["totals"];
+        assert_eq!(totals["num_commits"], 2, "{args:?}");
+        assert_eq!(totals["additions"], 7, "{args:?}");
+    }
+}
+
+#[test]
+fn json_reports_the_first_and_last_commits_as_rfc_3339() {
+    let repo = TestRepo::new("cli-rfc-3339");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    // 2024-01-02T23:30:00Z, which is already the 3rd in Tokyo
+    repo.write("b.txt", "b\n");
+    repo.git(["add", "-A"]);
+    repo.git([
+        "commit",
+        "-q",
+        "--author",
+        JANE,
+        "--date",
+        "@1704238200 +0900",
+        "-m",
+        "Add b",
+    ]);
+
+    let authors = run_json(&repo, &[]);
+    assert_eq!(
+        get_field(&authors, "first_commit"),
+        ["2024-01-01T12:00:00+00:00"]
+    );
+    assert_eq!(
+        get_field(&authors, "last_commit"),
+        ["2024-01-03T08:30:00+09:00"]
+    );
+
+    // The other formats only show the dates
+    let stdout = run_ok(&repo, &["--format", "csv"]);
+    let row = stdout.lines().nth(1).unwrap();
+    assert!(row.contains(",2024-01-01,2024-01-03,2,2,"), "{row}");
+}
+
+#[test]
+fn interactive_fails_outside_of_a_terminal() {
+    let repo = TestRepo::new("cli-interactive");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    let output = run(&repo, &["--interactive"]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'--interactive' needs a terminal"),
+        "{stderr}"
+    );
+}
+
+/// Create a repo with a few authors, a `.mailmap` alias and a merge made by
+/// an author that didn't make any other commit.
+fn create_golden_repo(name: &str) -> TestRepo {
+    let repo = TestRepo::new(name);
+    repo.write("a.txt", "a\nb\n");
+    repo.commit(JANE, "Add a");
+    repo.git(["checkout", "-q", "-b", "feature"]);
+    repo.write("b.txt", "1\n2\n3\n");
+    repo.commit(JOHN, "Add b");
+    repo.git(["checkout", "-q", "main"]);
+    repo.write("a.txt", "a\nc\n");
+    repo.commit("jane <jane@old.example.com>", "Update a");
+    repo.write(
+        ".mailmap",
+        "Jane Doe <jane@example.com> <jane@old.example.com>\n",
+    );
+    repo.commit(JOHN, "Add the mailmap");
+    repo.merge("feature", "Ann <ann@example.com>");
+    repo
+}
+
+/// Check the reports against the files of `tests/golden`, which must only change
+/// when the output is changed on purpose.
+#[test]
+fn output_matches_the_golden_files() {
+    let repo = create_golden_repo("cli-golden");
+
+    assert_eq!(run_ok(&repo, &[]), include_str!("golden/report.txt"));
+    assert_eq!(
+        run_ok(&repo, &["--format", "csv"]),
+        include_str!("golden/report.csv")
+    );
+    assert_eq!(
+        run_ok(&repo, &["--format", "json"]),
+        include_str!("golden/report.json")
+    );
+}
@@ -0,0 +1,159 @@
+//! A throwaway git repository to run the tests against.
+
+// Each test crate only uses part of the helpers
+#![allow(dead_code)]
+
+use std::cell::Cell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A git repository created under the temporary directory, and removed once dropped.
+/// Commits get increasing dates starting from 2024-01-01, so that the results are stable.
+pub struct TestRepo {
+    pub path: PathBuf,
+    num_commits: Cell<u32>,
+}
+
+impl TestRepo {
+    /// Create an empty repository whose default branch is `main`.
+    /// `name` must be unique across the tests, since they run in parallel.
+    pub fn new(name: &str) -> Self {
+        Self::with_branch(name, "main")
+    }
+
+    /// Create an empty repository whose default branch is `branch`.
+    pub fn with_branch(name: &str, branch: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("whos-done-that-test-{name}-{}", std::process::id()));
+        // Left over by a previous run that was interrupted
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        let repo = Self {
+            path,
+            num_commits: Cell::new(0),
+        };
+        repo.git(["init", "-q", "-b", branch]);
+        repo
+    }
+
+    /// Run git with `args` inside the repo and return its stdout.
+    /// Panic if it fails, since the tests can't go on without it.
+    pub fn git<I, S>(&self, args: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let output = git_command(&self.path)
+            .args(args)
+            .output()
+            .expect("git should be installed to run the tests");
+        assert!(
+            output.status.success(),
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Run git with `args` inside the repo, feeding it `stdin`, and return its stdout.
+    pub fn git_with_stdin(&self, args: &[&str], stdin: &[u8]) -> String {
+        let mut child = git_command(&self.path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Write `contents` to `path`, relative to the root of the repo.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.path.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    /// Commit every change of the working tree as `author` (e.g. `Jane <jane@example.com>`).
+    pub fn commit(&self, author: &str, message: &str) {
+        self.git(["add", "-A"]);
+        self.commit_staged(author, message);
+    }
+
+    /// Commit the staged changes as `author`, even if there are none.
+    pub fn commit_staged(&self, author: &str, message: &str) {
+        let date = self.next_date();
+        self.git([
+            "commit",
+            "-q",
+            "--allow-empty",
+            "--no-verify",
+            "--author",
+            author,
+            "--date",
+            &date,
+            "-m",
+            message,
+        ]);
+    }
+
+    /// Merge `branch` into the current branch with a merge commit made by `author`.
+    pub fn merge(&self, branch: &str, author: &str) {
+        let date = self.next_date();
+        let output = git_command(&self.path)
+            .args(["merge", "-q", "--no-ff", "--no-edit", branch])
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_AUTHOR_NAME", author_part(author, 0))
+            .env("GIT_AUTHOR_EMAIL", author_part(author, 1))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git merge failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// Return the date of the next commit, one day after the previous one.
+    fn next_date(&self) -> String {
+        let n = self.num_commits.get();
+        self.num_commits.set(n + 1);
+        // 2024-01-01 12:00:00 UTC
+        let timestamp = 1_704_110_400 + u64::from(n) * 24 * 60 * 60;
+        format!("@{timestamp} +0000")
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Return `Name` (with `index` 0) or `email` (with `index` 1) from `Name <email>`.
+fn author_part(author: &str, index: usize) -> &str {
+    let (name, email) = author.split_once('<').unwrap();
+    [name.trim(), email.trim_end_matches('>')][index]
+}
+
+/// Return a git command running inside `path`, isolated from the config of the user.
+fn git_command(path: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .current_dir(path)
+        .env(
+            "GIT_CONFIG_GLOBAL",
+            if cfg!(windows) { "NUL" } else { "/dev/null" },
+        )
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_COMMITTER_NAME", "Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+        .env("GIT_COMMITTER_DATE", "@1704110400 +0000")
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE");
+    command
+}
@@ -0,0 +1,435 @@
+//! Tests of the library against real git repositories.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::TestRepo;
+use whos_done_that::{
+    analyze, get_blame_data, get_commits, get_exclude_patterns, get_stdout_from_git_or_fail,
+    get_text_files, CodeEdits, Error, GitRepo, QueryOptions, Repo,
+};
+
+const JANE: &str = "Jane Doe <jane@example.com>";
+const JOHN: &str = "John Smith <john@example.com>";
+
+/// Return the options used by default by the command line.
+fn default_options() -> QueryOptions {
+    QueryOptions {
+        use_mailmap: true,
+        respect_gitattributes: true,
+        follow_renames: true,
+        ..Default::default()
+    }
+}
+
+/// Return the name, commits, additions and removals of each author of `repo`.
+fn get_stats(repo: &TestRepo, options: &QueryOptions) -> Vec<(String, usize, usize, usize)> {
+    analyze(&repo.path, options)
+        .unwrap()
+        .into_iter()
+        .map(|a| {
+            let edits = a.code_edits;
+            (
+                a.author_name,
+                a.num_commits,
+                edits.additions,
+                edits.removals,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn analyze_counts_the_commits_and_lines_of_each_author() {
+    let repo = TestRepo::new("analyze");
+    repo.write("src/main.rs", "fn main() {\n    println!(\"Hi\");\n}\n");
+    repo.commit(JANE, "First commit");
+    repo.write("src/main.rs", "fn main() {\n    println!(\"Hello\");\n}\n");
+    repo.write("README.md", "# Hello\n\nSays hello.\n");
+    repo.commit(JOHN, "Say hello");
+    repo.write("README.md", "# Hello\n");
+    repo.commit(JANE, "Shorten the README");
+
+    let authors_data = analyze(&repo.path, &default_options()).unwrap();
+
+    assert_eq!(authors_data.len(), 2);
+    let jane = &authors_data[0];
+    assert_eq!(jane.author_name, "Jane Doe");
+    assert_eq!(jane.author_email.as_deref(), Some("jane@example.com"));
+    assert_eq!(jane.num_commits, 2);
+    assert_eq!(jane.code_edits.additions, 3);
+    assert_eq!(jane.code_edits.removals, 2);
+    assert_eq!(
+        jane.first_commit.as_deref(),
+        Some("2024-01-01T12:00:00+00:00")
+    );
+    assert_eq!(
+        jane.last_commit.as_deref(),
+        Some("2024-01-03T12:00:00+00:00")
+    );
+    assert_eq!(jane.first_commit_date(), Some("2024-01-01"));
+    assert_eq!(jane.last_commit_date(), Some("2024-01-03"));
+    assert_eq!(jane.active_days, 2);
+    assert_eq!(jane.active_span_days, 2);
+
+    let john = &authors_data[1];
+    assert_eq!(john.author_name, "John Smith");
+    assert_eq!(john.num_commits, 1);
+    assert_eq!(john.code_edits.additions, 4);
+    assert_eq!(john.code_edits.removals, 1);
+
+    let total_percent: f64 = authors_data.iter().map(|a| a.percent_commits).sum();
+    assert!((total_percent - 100.0).abs() < 1e-9);
+}
+
+#[test]
+fn git_repo_answers_the_same_counts_as_analyze() {
+    let repo = TestRepo::new("git-repo");
+    repo.write("a.txt", "1\n2\n");
+    repo.commit(JANE, "Add a");
+    repo.write("b.txt", "1\n2\n3\n");
+    repo.commit(JOHN, "Add b");
+    repo.write("a.txt", "1\n");
+    repo.commit(JANE, "Shorten a");
+
+    let git_repo = GitRepo::open(&repo.path, &default_options()).unwrap();
+    let authors = git_repo.authors().unwrap();
+    assert_eq!(
+        authors,
+        [
+            ("Jane Doe".to_string(), "jane@example.com".to_string()),
+            ("John Smith".to_string(), "john@example.com".to_string()),
+        ]
+    );
+
+    let stats: Vec<(String, usize, usize, usize)> = authors
+        .into_iter()
+        .map(|(name, email)| {
+            let edits = git_repo.edits(&email).unwrap();
+            let num_commits = git_repo.commit_count(&email).unwrap();
+            (name, num_commits, edits.additions, edits.removals)
+        })
+        .collect();
+    assert_eq!(stats, get_stats(&repo, &default_options()));
+
+    assert_eq!(git_repo.commit_count("nobody@example.com").unwrap(), 0);
+    assert_eq!(
+        git_repo.edits("nobody@example.com").unwrap(),
+        CodeEdits::default()
+    );
+}
+
+#[test]
+fn merges_are_only_counted_when_requested() {
+    let repo = TestRepo::new("merges");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+    repo.git(["checkout", "-q", "-b", "feature"]);
+    repo.write("b.txt", "b\n");
+    repo.commit(JOHN, "Add b");
+    repo.git(["checkout", "-q", "main"]);
+    repo.write("c.txt", "c\n");
+    repo.commit(JANE, "Add c");
+    repo.merge("feature", JANE);
+
+    assert_eq!(
+        get_stats(&repo, &default_options()),
+        vec![
+            ("Jane Doe".to_string(), 2, 2, 0),
+            ("John Smith".to_string(), 1, 1, 0),
+        ]
+    );
+
+    let options = QueryOptions {
+        include_merges: true,
+        ..default_options()
+    };
+    // Merges don't report any line, since they have more than one parent
+    assert_eq!(
+        get_stats(&repo, &options),
+        vec![
+            ("Jane Doe".to_string(), 3, 2, 0),
+            ("John Smith".to_string(), 1, 1, 0),
+        ]
+    );
+}
+
+#[test]
+fn get_commits_reads_the_file_changes_of_each_commit() {
+    let repo = TestRepo::new("file-changes");
+    repo.write("notes.txt", "one\ntwo\n");
+    repo.write("logo.bin", "\0\u{1}\u{2}");
+    repo.commit(JANE, "Add the notes and the logo");
+    repo.write("notes.txt", "one\nthree\n");
+    repo.commit(JOHN, "Update the notes");
+
+    let mut num_parsed = 0;
+    let commits = get_commits(&repo.path, &default_options(), |_| num_parsed += 1).unwrap();
+
+    assert_eq!(num_parsed, 2);
+    // From the most recent to the oldest
+    assert_eq!(commits[0].author_name, "John Smith");
+    assert_eq!(commits[0].date, "2024-01-02");
+    assert_eq!(commits[0].file_changes.len(), 1);
+    assert_eq!(commits[0].file_changes[0].path, "notes.txt");
+    assert_eq!(commits[0].file_changes[0].additions, 1);
+    assert_eq!(commits[0].file_changes[0].removals, 1);
+
+    assert_eq!(commits[1].author_name, "Jane Doe");
+    let mut file_changes = commits[1].file_changes.clone();
+    file_changes.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(file_changes[0].path, "logo.bin");
+    assert!(file_changes[0].is_binary);
+    assert_eq!(file_changes[1].path, "notes.txt");
+    assert_eq!(file_changes[1].additions, 2);
+}
+
+#[test]
+fn commits_only_touching_vendored_files_dont_count() {
+    let repo = TestRepo::new("gitattributes");
+    repo.write(
+        ".gitattributes",
+        "third_party/** linguist-vendored\n*.pb.go linguist-generated=true\n",
+    );
+    repo.write("main.go", "package main\n");
+    repo.commit(JANE, "Add main");
+    repo.write("third_party/lib/lib.go", "package lib\n\nvar X = 1\n");
+    repo.write("api/api.pb.go", "package api\n");
+    repo.commit(JOHN, "Run the codegen");
+    repo.write("api/api.pb.go", "package api\n\nvar Y = 2\n");
+    repo.write("api/api.go", "package api\n");
+    repo.commit(JOHN, "Add the API");
+    repo.commit_staged(JANE, "Empty commit");
+
+    assert_eq!(
+        get_stats(&repo, &default_options()),
+        vec![
+            ("Jane Doe".to_string(), 2, 3, 0),
+            ("John Smith".to_string(), 1, 1, 0),
+        ]
+    );
+
+    let options = QueryOptions {
+        respect_gitattributes: false,
+        ..default_options()
+    };
+    assert_eq!(
+        get_stats(&repo, &options),
+        vec![
+            ("Jane Doe".to_string(), 2, 3, 0),
+            ("John Smith".to_string(), 2, 7, 0),
+        ]
+    );
+}
+
+#[test]
+fn ignore_whitespace_skips_the_reindented_lines() {
+    let repo = TestRepo::new("ignore-whitespace");
+    repo.write("main.rs", "fn main() {\nprintln!(\"Hi\");\nlet x = 1;\n}\n");
+    repo.commit(JANE, "Add main");
+    repo.write(
+        "main.rs",
+        "fn main() {\n    println!(\"Hi\");\n    let x = 1;\n}\n",
+    );
+    repo.commit(JOHN, "Reindent main");
+
+    assert_eq!(
+        get_stats(&repo, &default_options()),
+        vec![
+            ("Jane Doe".to_string(), 1, 4, 0),
+            ("John Smith".to_string(), 1, 2, 2),
+        ]
+    );
+
+    let options = QueryOptions {
+        ignore_whitespace: true,
+        ..default_options()
+    };
+    assert_eq!(
+        get_stats(&repo, &options),
+        vec![
+            ("Jane Doe".to_string(), 1, 4, 0),
+            ("John Smith".to_string(), 1, 0, 0),
+        ]
+    );
+}
+
+#[test]
+fn renaming_a_file_doesnt_credit_its_lines_to_the_mover() {
+    let repo = TestRepo::new("renames");
+    // Renames are detected even if they were disabled in the config of the repo
+    repo.git(["config", "diff.renames", "false"]);
+    repo.write("old.rs", &"let x = 1;\n".repeat(20));
+    repo.commit(JANE, "Add old.rs");
+    repo.git(["mv", "old.rs", "new.rs"]);
+    repo.commit(JOHN, "Rename old.rs to new.rs");
+
+    assert_eq!(
+        get_stats(&repo, &default_options()),
+        vec![
+            ("Jane Doe".to_string(), 1, 20, 0),
+            ("John Smith".to_string(), 1, 0, 0),
+        ]
+    );
+
+    let commits = get_commits(&repo.path, &default_options(), |_| {}).unwrap();
+    let rename = &commits[0].file_changes[0];
+    assert_eq!(rename.path, "new.rs");
+    assert_eq!(rename.renamed_from.as_deref(), Some("old.rs"));
+
+    let options = QueryOptions {
+        follow_renames: false,
+        ..default_options()
+    };
+    assert_eq!(
+        get_stats(&repo, &options),
+        vec![
+            ("Jane Doe".to_string(), 1, 20, 0),
+            ("John Smith".to_string(), 1, 20, 20),
+        ]
+    );
+}
+
+#[test]
+fn get_blame_data_counts_the_lines_each_author_touched_last() {
+    let repo = TestRepo::new("blame");
+    repo.write("a.txt", "1\n2\n3\n4\n");
+    repo.write("logo.bin", "\0\u{1}");
+    repo.commit(JANE, "Add a");
+    repo.write("a.txt", "1\ntwo\n3\n4\n");
+    repo.write("docs/b.txt", "b\n");
+    repo.commit(JOHN, "Update a");
+
+    let mut files = get_text_files(&repo.path, &default_options()).unwrap();
+    files.sort();
+    assert_eq!(files, ["a.txt", "docs/b.txt"]);
+
+    let options = QueryOptions {
+        paths: vec!["docs".to_string()],
+        ..default_options()
+    };
+    assert_eq!(
+        get_text_files(&repo.path, &options).unwrap(),
+        ["docs/b.txt"]
+    );
+
+    let num_blamed = AtomicUsize::new(0);
+    let mut blame_data = get_blame_data(&repo.path, &default_options(), &files, |_| {
+        num_blamed.fetch_add(1, Ordering::Relaxed);
+    });
+    blame_data.sort_by(|a, b| a.author_name.cmp(&b.author_name));
+
+    assert_eq!(num_blamed.into_inner(), 2);
+    let lines: Vec<(&str, usize)> = blame_data
+        .iter()
+        .map(|b| (b.author_name.as_str(), b.lines_owned))
+        .collect();
+    assert_eq!(lines, [("Jane Doe", 3), ("John Smith", 2)]);
+
+    // Blaming one file at a time doesn't change the results
+    let options = QueryOptions {
+        jobs: 1,
+        ..default_options()
+    };
+    let mut sequential_blame_data = get_blame_data(&repo.path, &options, &files, |_| {});
+    sequential_blame_data.sort_by(|a, b| a.author_name.cmp(&b.author_name));
+    assert_eq!(sequential_blame_data, blame_data);
+}
+
+#[test]
+fn error_not_a_git_repo() {
+    let repo = TestRepo::new("error-not-a-repo");
+    std::fs::remove_dir_all(repo.path.join(".git")).unwrap();
+
+    let error = analyze(&repo.path, &default_options()).unwrap_err();
+    assert!(
+        matches!(&error, Error::NotAGitRepo(path) if *path == repo.path),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn error_unknown_revision() {
+    let repo = TestRepo::new("error-unknown-revision");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    for revision in ["release/2.x", "v1.0..HEAD"] {
+        let options = QueryOptions {
+            branch_name: revision.to_string(),
+            ..default_options()
+        };
+        let error = analyze(&repo.path, &options).unwrap_err();
+        assert!(
+            matches!(&error, Error::UnknownRevision(r) if r == revision),
+            "{error:?}"
+        );
+    }
+}
+
+#[test]
+fn error_git_failed_keeps_what_git_said() {
+    let repo = TestRepo::new("error-git-failed");
+
+    let error = get_stdout_from_git_or_fail(&repo.path, ["cat-file", "-p", "0123456"]).unwrap_err();
+    match error {
+        Error::GitFailed { command, stderr } => {
+            assert_eq!(command, "git cat-file -p 0123456");
+            assert!(stderr.contains("0123456"), "{stderr}");
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn error_invalid_pattern() {
+    let error = get_exclude_patterns(&["/[bot/".to_string()], false).unwrap_err();
+    assert!(
+        matches!(&error, Error::InvalidPattern { pattern, .. } if pattern == "[bot"),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn error_io() {
+    let repo = TestRepo::new("error-io");
+    let missing_dir = repo.path.join("missing");
+
+    let error = get_commits(&missing_dir, &default_options(), |_| {}).unwrap_err();
+    assert!(
+        matches!(&error, Error::Io(e) if e.kind() == std::io::ErrorKind::NotFound),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn non_utf8_author_names_dont_discard_the_other_authors() {
+    let repo = TestRepo::new("non-utf8");
+    repo.write("a.txt", "a\n");
+    repo.commit(JANE, "Add a");
+
+    // `git commit` converts the names to UTF-8, so the commit is written by hand
+    repo.write("b.txt", "b\n");
+    repo.git(["add", "-A"]);
+    let tree = repo.git(["write-tree"]);
+    let parent = repo.git(["rev-parse", "HEAD"]);
+    let mut commit = format!("tree {}\nparent {}\n", tree.trim(), parent.trim()).into_bytes();
+    // "José" encoded as Latin-1
+    commit.extend_from_slice(b"author Jos\xe9 <jose@example.com> 1704200000 +0000\n");
+    commit.extend_from_slice(b"committer Jos\xe9 <jose@example.com> 1704200000 +0000\n\nAdd b\n");
+    let commit_id = repo.git_with_stdin(&["hash-object", "-t", "commit", "-w", "--stdin"], &commit);
+    repo.git(["reset", "-q", "--soft", commit_id.trim()]);
+
+    repo.write("c.txt", "c\n");
+    repo.commit(JOHN, "Add c");
+
+    assert_eq!(
+        get_stats(&repo, &default_options()),
+        vec![
+            ("Jane Doe".to_string(), 1, 1, 0),
+            ("John Smith".to_string(), 1, 1, 0),
+            ("Jos\u{fffd}".to_string(), 1, 1, 0),
+        ]
+    );
+}